@@ -0,0 +1,83 @@
+//! Compares the ways of running the example rules on a synthetic corpus.
+//!
+//! Run with `cargo bench --bench strategies`. Set `LINTOKO_BENCH_FILES` to change the size of the corpus.
+
+use lintoko::{Config, Linter, Strategy, lint, load_rules_from_directory};
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Copies of the test data per file, so files are closer to the size of real-world modules
+const COPIES_PER_FILE: usize = 5;
+const ITERATIONS: usize = 5;
+
+fn corpus(files: usize) -> Vec<(String, String)> {
+    let test_data = include_str!("../test-data.mo");
+    (0..files)
+        .map(|i| (format!("src/file{i}.mo"), test_data.repeat(COPIES_PER_FILE)))
+        .collect()
+}
+
+/// The fastest of `ITERATIONS` runs
+fn measure(mut run: impl FnMut() -> usize) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut findings = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        findings = black_box(run());
+        best = best.min(start.elapsed());
+    }
+    (best, findings)
+}
+
+fn main() {
+    let files = std::env::var("LINTOKO_BENCH_FILES")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(200);
+    let rules = load_rules_from_directory(Path::new("example-rules")).unwrap();
+    let corpus = corpus(files);
+    let config = Config::default();
+    println!(
+        "{} rules, {files} files with {} lines each",
+        rules.len(),
+        corpus[0].1.lines().count()
+    );
+
+    let lint_with = |strategy: Strategy| {
+        let mut linter = Linter::new(&rules).with_strategy(strategy);
+        corpus
+            .iter()
+            .map(|(path, source)| {
+                linter
+                    .lint(&config, path, source)
+                    .unwrap()
+                    .diagnostics
+                    .len()
+            })
+            .sum()
+    };
+    let benchmarks: [(&str, &dyn Fn() -> usize); 3] = [
+        ("lint() per file", &|| {
+            corpus
+                .iter()
+                .map(|(path, source)| {
+                    lint(&config, path, source, &rules)
+                        .unwrap()
+                        .diagnostics
+                        .len()
+                })
+                .sum()
+        }),
+        ("Linter, per rule", &|| lint_with(Strategy::PerRule)),
+        ("Linter, combined", &|| lint_with(Strategy::Combined)),
+    ];
+    for (name, run) in benchmarks {
+        let (time, findings) = measure(run);
+        println!(
+            "{name:<20} {:>10.2?} ({:.2?} per file, {findings} findings)",
+            time,
+            time / files as u32
+        );
+    }
+}
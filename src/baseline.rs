@@ -0,0 +1,252 @@
+use crate::linter::apply_fixes;
+use crate::{Diagnostic, LintResult};
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+/// Bumped whenever the shape of the baseline file changes incompatibly
+pub const BASELINE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub path: String,
+    pub rule: String,
+    pub fingerprint: String,
+    /// The first line of the matched text, so the baseline can be reviewed. Not used for matching
+    pub excerpt: String,
+    /// How many findings in the file share the fingerprint
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    version: u32,
+    entries: Vec<BaselineEntry>,
+}
+
+type Key = (String, String, String);
+
+/// Known findings that shouldn't fail the run, e.g. violations of a newly added rule in legacy code
+#[derive(Debug, Default)]
+pub struct Baseline {
+    entries: BTreeMap<Key, BaselineEntry>,
+    /// How many findings of each entry haven't been seen in this run yet
+    remaining: BTreeMap<Key, usize>,
+    linted: BTreeSet<String>,
+}
+
+// Baselines are shared between machines, so they shouldn't depend on the path separator
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// Identifies a finding by its rule, the matched text and where in the syntax tree it is, instead of
+/// its position. This way it survives unrelated edits to the rest of the file, including re-indentation.
+fn fingerprint(source: &str, diagnostic: &Diagnostic) -> String {
+    let matched = source
+        .get(diagnostic.range.start_byte..diagnostic.range.end_byte)
+        .unwrap_or_default();
+    let matched = matched.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut hasher = Sha256::new();
+    for part in [&diagnostic.rule, &matched, &diagnostic.context] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn excerpt(source: &str, diagnostic: &Diagnostic) -> String {
+    let matched = source
+        .get(diagnostic.range.start_byte..diagnostic.range.end_byte)
+        .unwrap_or_default();
+    let line = matched.lines().next().unwrap_or_default().trim();
+    match line.char_indices().nth(80) {
+        Some((i, _)) => format!("{}...", &line[..i]),
+        None => line.to_string(),
+    }
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| anyhow!("Failed to read baseline from '{}'", path.display()))?;
+        Self::from_json(&content)
+            .with_context(|| anyhow!("Failed to parse baseline from '{}'", path.display()))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let document: Document = serde_json::from_str(json)?;
+        if document.version != BASELINE_VERSION {
+            bail!(
+                "Unsupported baseline version {}, expected {BASELINE_VERSION}. Re-create it with `--write-baseline`",
+                document.version
+            )
+        }
+        let mut baseline = Self::default();
+        for entry in document.entries {
+            let key = (
+                entry.path.clone(),
+                entry.rule.clone(),
+                entry.fingerprint.clone(),
+            );
+            baseline.remaining.insert(key.clone(), entry.count);
+            baseline.entries.insert(key, entry);
+        }
+        Ok(baseline)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.values().map(|e| e.count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds all diagnostics of a file to the baseline
+    pub fn record(&mut self, path: &str, source: &str, diagnostics: &[Diagnostic]) {
+        let path = normalize_path(path);
+        for d in diagnostics {
+            let fingerprint = fingerprint(source, d);
+            let key = (path.clone(), d.rule.clone(), fingerprint.clone());
+            self.entries
+                .entry(key)
+                .or_insert_with(|| BaselineEntry {
+                    path: path.clone(),
+                    rule: d.rule.clone(),
+                    fingerprint,
+                    excerpt: excerpt(source, d),
+                    count: 0,
+                })
+                .count += 1;
+        }
+    }
+
+    /// Removes the diagnostics that are in the baseline from `result`, together with their fixes, and
+    /// updates its counts
+    pub fn apply(&mut self, path: &str, source: &str, result: &mut LintResult) {
+        let path = normalize_path(path);
+        result.diagnostics.retain(|d| {
+            let key = (path.clone(), d.rule.clone(), fingerprint(source, d));
+            match self.remaining.get_mut(&key) {
+                Some(remaining) if *remaining > 0 => {
+                    *remaining -= 1;
+                    false
+                }
+                _ => true,
+            }
+        });
+        result.count_severities();
+        // Without `--fix`, there's neither a fixed file nor overlapping fixes
+        if result.fixed_file.is_some() || result.fix_overlaps {
+            (result.fixed_file, result.fix_overlaps) = apply_fixes(source, &result.diagnostics);
+        }
+        self.linted.insert(path);
+    }
+
+    /// Entries for linted files that didn't show up anymore, and can be dropped from the baseline
+    pub fn fixed(&self) -> Vec<(&BaselineEntry, usize)> {
+        self.remaining
+            .iter()
+            .filter(|((path, _, _), remaining)| **remaining > 0 && self.linted.contains(path))
+            .map(|(key, remaining)| (&self.entries[key], *remaining))
+            .collect()
+    }
+
+    pub fn write(&self, mut out: impl Write) -> Result<()> {
+        let document = Document {
+            version: BASELINE_VERSION,
+            entries: self.entries.values().cloned().collect(),
+        };
+        serde_json::to_writer_pretty(&mut out, &document)?;
+        writeln!(&mut out)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Config, lint, load_rule_from_file};
+
+    fn lint_pun_fields_with(config: &Config, source: &str) -> LintResult {
+        let rules = [load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap()];
+        lint(config, "src\\main.mo", source, &rules).unwrap()
+    }
+
+    fn lint_pun_fields(source: &str) -> LintResult {
+        lint_pun_fields_with(&Config::default(), source)
+    }
+
+    fn baseline_for(source: &str) -> Baseline {
+        let mut baseline = Baseline::default();
+        baseline.record("src\\main.mo", source, &lint_pun_fields(source).diagnostics);
+        let mut json: Vec<u8> = vec![];
+        baseline.write(&mut json).unwrap();
+        Baseline::from_json(str::from_utf8(&json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn it_round_trips_entries() {
+        let baseline = baseline_for("let a = { x = x; y = y };\nlet b = { x = x };");
+        assert_eq!(baseline.len(), 3);
+        let entries: Vec<_> = baseline.entries.values().collect();
+        assert_eq!(entries.len(), 2, "identical findings share an entry");
+        assert!(entries.iter().all(|e| e.path == "src/main.mo"));
+        assert!(entries.iter().any(|e| e.excerpt == "x = x" && e.count == 2));
+    }
+
+    #[test]
+    fn it_only_reports_new_findings_after_unrelated_edits() {
+        let mut baseline = baseline_for("let a = { x = x };");
+        let source = "let c = 1;\n\nlet a = { x = x };\nlet b = { y = y };";
+        let mut result = lint_pun_fields(source);
+        baseline.apply("src/main.mo", source, &mut result);
+        assert_eq!(result.warning_count, 1);
+        assert_eq!(result.diagnostics[0].range.start_point.row, 3);
+        assert!(baseline.fixed().is_empty());
+    }
+
+    #[test]
+    fn it_lists_fixed_entries() {
+        let mut baseline = baseline_for("let a = { x = x };\nlet b = { y = y };");
+        let source = "let a = { x };\nlet b = { y = y };";
+        let mut result = lint_pun_fields(source);
+        baseline.apply("src/main.mo", source, &mut result);
+        assert!(result.diagnostics.is_empty());
+        let fixed = baseline.fixed();
+        assert_eq!(fixed.len(), 1);
+        assert_eq!(fixed[0].0.excerpt, "x = x");
+        assert_eq!(fixed[0].1, 1);
+    }
+
+    #[test]
+    fn it_only_fixes_new_findings() {
+        let mut baseline = baseline_for("let a = { x = x };");
+        let source = "let a = { x = x };\nlet b = { y = y };";
+        let config = Config {
+            fix: true,
+            ..Config::default()
+        };
+        let mut result = lint_pun_fields_with(&config, source);
+        baseline.apply("src/main.mo", source, &mut result);
+        assert_eq!(
+            result.fixed_file.as_deref(),
+            Some("let a = { x = x };\nlet b = { y };")
+        );
+
+        let mut baseline = baseline_for(source);
+        let mut result = lint_pun_fields_with(&config, source);
+        baseline.apply("src/main.mo", source, &mut result);
+        assert_eq!(result.fixed_file, None);
+    }
+
+    #[test]
+    fn it_rejects_other_versions() {
+        let err = Baseline::from_json(r#"{ "version": 0, "entries": [] }"#).unwrap_err();
+        assert!(err.to_string().contains("Unsupported baseline version 0"));
+    }
+}
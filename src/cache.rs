@@ -0,0 +1,405 @@
+use crate::linter::finish;
+use crate::{Config, Diagnostic, Escalation, LintResult, Linter, Range, Rule, RuleSeverity};
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::debug;
+
+/// Name of the cache directory, created next to the closest `lintoko.toml`
+pub const CACHE_DIR: &str = ".lintoko-cache";
+
+/// Bumped whenever the shape of the cache entries changes
+const CACHE_VERSION: u32 = 1;
+
+/// Must match the `rev` of `tree-sitter-motoko` in Cargo.toml, as grammar changes can change the findings
+const GRAMMAR_REVISION: &str = "9de81ac8acee791b238727dabb6beec0c3a6eb0a";
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    diagnostics: Vec<CachedDiagnostic>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedDiagnostic {
+    rule: String,
+    description: String,
+    start_byte: usize,
+    end_byte: usize,
+    /// Row and column
+    start: (usize, usize),
+    end: (usize, usize),
+    fix: Option<String>,
+    severity: RuleSeverity,
+    context: String,
+    escalation: Option<(RuleSeverity, String)>,
+}
+
+impl From<&Diagnostic> for CachedDiagnostic {
+    fn from(d: &Diagnostic) -> Self {
+        Self {
+            rule: d.rule.clone(),
+            description: d.description.clone(),
+            start_byte: d.range.start_byte,
+            end_byte: d.range.end_byte,
+            start: (d.range.start_point.row, d.range.start_point.column),
+            end: (d.range.end_point.row, d.range.end_point.column),
+            fix: d.fix.clone(),
+            severity: d.severity,
+            context: d.context.clone(),
+            escalation: d.escalation.map(|e| (e.to, e.after.to_string())),
+        }
+    }
+}
+
+impl CachedDiagnostic {
+    fn into_diagnostic(self) -> Result<Diagnostic> {
+        let escalation = match self.escalation {
+            Some((to, after)) => Some(Escalation {
+                to,
+                after: after.parse()?,
+            }),
+            None => None,
+        };
+        Ok(Diagnostic {
+            rule: self.rule,
+            description: self.description,
+            range: Range {
+                start_byte: self.start_byte,
+                end_byte: self.end_byte,
+                start_point: tree_sitter::Point::new(self.start.0, self.start.1),
+                end_point: tree_sitter::Point::new(self.end.0, self.end.1),
+            },
+            fix: self.fix,
+            severity: self.severity,
+            context: self.context,
+            escalation,
+        })
+    }
+}
+
+fn update(hasher: &mut Sha256, part: &str) {
+    hasher.update(part.as_bytes());
+    hasher.update([0]);
+}
+
+fn hash_rule(hasher: &mut Sha256, rule: &Rule) {
+    // Destructured, so a new field can't be left out of the key by accident
+    let Rule {
+        name,
+        description,
+        query,
+        fix,
+        severity,
+        includes,
+        excludes,
+        escalate,
+    } = rule;
+    update(hasher, name);
+    update(hasher, description);
+    update(hasher, query);
+    update(hasher, fix.as_deref().unwrap_or("\0none"));
+    update(hasher, &severity.to_string());
+    for pattern in includes.iter().chain(excludes) {
+        update(hasher, pattern.as_str());
+    }
+    update(hasher, &includes.len().to_string());
+    match escalate {
+        Some(e) => update(hasher, &format!("{} {}", e.to, e.after)),
+        None => update(hasher, "\0none"),
+    }
+}
+
+impl Rule {
+    /// A hash of everything about the rule that can change its findings, to tell whether a rule was edited
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        hash_rule(&mut hasher, self);
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// Stores the diagnostics of linted files on disk, so unchanged files don't need to be parsed again.
+///
+/// Entries are keyed by the file contents, every rule that runs on the file, the options that change the
+/// findings, the lintoko version and the grammar revision. Editing a rule only invalidates the entries of
+/// the files it runs on. Stale entries aren't removed, use `Cache::clear` for that.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    hits: AtomicUsize,
+}
+
+impl Cache {
+    /// Opens the cache in `dir`, creating it if it doesn't exist yet
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| anyhow!("Failed to create cache directory at '{}'", dir.display()))?;
+        // Keeps the cache out of version control and backups
+        for (name, content) in [
+            (".gitignore", "*\n"),
+            (
+                "CACHEDIR.TAG",
+                "Signature: 8a477f597d28d172789f06886806bc55\n# Created by lintoko\n",
+            ),
+        ] {
+            let path = dir.join(name);
+            if !path.exists() {
+                fs::write(&path, content)
+                    .with_context(|| anyhow!("Failed to write '{}'", path.display()))?;
+            }
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            hits: AtomicUsize::new(0),
+        })
+    }
+
+    /// Removes the cache in `dir` with all its entries
+    pub fn clear(dir: &Path) -> Result<()> {
+        match fs::remove_dir_all(dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(err).with_context(|| anyhow!("Failed to remove cache at '{}'", dir.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// How many files were replayed from the cache so far
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    fn key(
+        &self,
+        config: &Config,
+        linter: &Linter,
+        path: &str,
+        input: &str,
+        include: &impl Fn(&Rule) -> bool,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        update(&mut hasher, &CACHE_VERSION.to_string());
+        update(&mut hasher, env!("CARGO_PKG_VERSION"));
+        update(&mut hasher, GRAMMAR_REVISION);
+        update(&mut hasher, input);
+        let severity_override = config.severity_override.map(|s| s.to_string());
+        update(
+            &mut hasher,
+            severity_override.as_deref().unwrap_or("\0none"),
+        );
+        // Suppressions can expire
+        if input.contains("lintoko-") {
+            update(&mut hasher, &config.today().to_string());
+        }
+        if config.audit_suppressions {
+            // Suppressions are checked against the names of all rules
+            update(&mut hasher, "\0audit");
+            for name in linter.known_rules() {
+                update(&mut hasher, name);
+            }
+        }
+        let today = config.today();
+        let running = linter.rules().iter().filter(|rule| {
+            rule.severity_on(today) != RuleSeverity::Off && rule.applies_to(path) && include(rule)
+        });
+        for rule in running {
+            hash_rule(&mut hasher, rule);
+            // Escalations depend on the date
+            update(&mut hasher, &rule.severity_on(today).to_string());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn load(&self, key: &str) -> Option<Vec<Diagnostic>> {
+        let path = self.dir.join(format!("{key}.json"));
+        let content = fs::read_to_string(&path).ok()?;
+        let entry: Result<Vec<Diagnostic>> = serde_json::from_str::<Entry>(&content)
+            .map_err(Into::into)
+            .and_then(|entry| {
+                entry
+                    .diagnostics
+                    .into_iter()
+                    .map(CachedDiagnostic::into_diagnostic)
+                    .collect()
+            });
+        // A broken entry is just a miss, it gets overwritten with the fresh result
+        entry
+            .inspect_err(|err| debug!("Ignoring cache entry at '{}': {err:#}", path.display()))
+            .ok()
+    }
+
+    fn store(&self, key: &str, diagnostics: &[Diagnostic]) {
+        static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+        let path = self.dir.join(format!("{key}.json"));
+        let entry = Entry {
+            diagnostics: diagnostics.iter().map(Into::into).collect(),
+        };
+        // Written to a temporary file and renamed, so other threads and runs never read a partial entry
+        let temp = self.dir.join(format!(
+            "{key}.{}-{}.tmp",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = serde_json::to_string(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                fs::write(&temp, json)?;
+                fs::rename(&temp, &path)?;
+                Ok(())
+            });
+        if let Err(err) = written {
+            let _ = fs::remove_file(&temp);
+            debug!(
+                "Failed to write cache entry at '{}': {err:#}",
+                path.display()
+            );
+        }
+    }
+
+    /// Like `Linter::lint_filtered`, but replays the diagnostics from the cache if the file and the rules
+    /// running on it haven't changed. Fixes are re-applied from the cached diagnostics.
+    pub fn lint(
+        &self,
+        linter: &mut Linter,
+        config: &Config,
+        path: &str,
+        input: &str,
+        include: impl Fn(&Rule) -> bool,
+    ) -> Result<LintResult> {
+        let key = self.key(config, linter, path, input, &include);
+        if let Some(diagnostics) = self.load(&key) {
+            debug!("Replaying cached results for: {path}");
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(finish(config, input, diagnostics, vec![]));
+        }
+        let result = linter.lint_filtered(config, path, input, include)?;
+        // Failed rules are retried on the next run
+        if result.tool_diagnostics.is_empty() {
+            self.store(&key, &result.diagnostics);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{load_rule_from_file, test_dir};
+
+    fn cache(name: &str) -> Cache {
+        Cache::open(&test_dir(name)).unwrap()
+    }
+
+    fn entries(cache: &Cache) -> Vec<PathBuf> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(&cache.dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| !p.ends_with(".gitignore") && !p.ends_with("CACHEDIR.TAG"))
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    fn summary(result: &LintResult) -> Vec<String> {
+        result
+            .diagnostics
+            .iter()
+            .map(|d| format!("{d:?}"))
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_files_are_replayed() {
+        let cache = cache("replay");
+        let rules = [load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap()];
+        let mut linter = Linter::new(&rules);
+        let config = Config {
+            fix: true,
+            ..Config::default()
+        };
+        let source = "let a = { x = x };\nlet b = { y = y };";
+        let fresh = cache
+            .lint(&mut linter, &config, "main.mo", source, |_| true)
+            .unwrap();
+        assert_eq!(cache.hits(), 0);
+
+        let replayed = cache
+            .lint(&mut linter, &config, "main.mo", source, |_| true)
+            .unwrap();
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(summary(&replayed), summary(&fresh));
+        assert_eq!(replayed.warning_count, 2);
+        assert_eq!(
+            replayed.fixed_file.as_deref(),
+            Some("let a = { x };\nlet b = { y };")
+        );
+
+        cache
+            .lint(&mut linter, &config, "main.mo", "let a = { x };", |_| true)
+            .unwrap();
+        assert_eq!(cache.hits(), 1, "changed files are linted again");
+    }
+
+    #[test]
+    fn changed_rules_invalidate_entries() {
+        let cache = cache("rules");
+        let rule = load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap();
+        let source = "let a = { x = x };";
+        let config = Config::default();
+        let mut linter = Linter::new(std::slice::from_ref(&rule));
+        cache
+            .lint(&mut linter, &config, "main.mo", source, |_| true)
+            .unwrap();
+
+        let changed = Rule {
+            severity: RuleSeverity::Error,
+            ..rule.clone()
+        };
+        let mut linter = Linter::new(&[changed]);
+        let result = cache.lint(&mut linter, &config, "main.mo", source, |_| true);
+        assert_eq!(cache.hits(), 0);
+        assert_eq!(result.unwrap().error_count, 1);
+
+        let mut linter = Linter::new(&[rule]);
+        let result = cache.lint(&mut linter, &config, "main.mo", source, |_| false);
+        assert_eq!(
+            cache.hits(),
+            0,
+            "the rules running on the file are part of the key"
+        );
+        assert!(result.unwrap().diagnostics.is_empty());
+    }
+
+    #[test]
+    fn broken_entries_are_misses() {
+        let cache = cache("broken");
+        let rules = [load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap()];
+        let mut linter = Linter::new(&rules);
+        let config = Config::default();
+        let source = "let a = { x = x };";
+        cache
+            .lint(&mut linter, &config, "main.mo", source, |_| true)
+            .unwrap();
+        let [entry] = &entries(&cache)[..] else {
+            panic!("expected a single entry, no temporary files");
+        };
+        fs::write(entry, "{\"diagnostics\": [").unwrap();
+
+        let result = cache.lint(&mut linter, &config, "main.mo", source, |_| true);
+        assert_eq!(cache.hits(), 0);
+        assert_eq!(result.unwrap().warning_count, 1);
+        // The broken entry was replaced
+        cache
+            .lint(&mut linter, &config, "main.mo", source, |_| true)
+            .unwrap();
+        assert_eq!(cache.hits(), 1);
+    }
+
+    #[test]
+    fn grammar_revision_matches_the_manifest() {
+        assert!(include_str!("../Cargo.toml").contains(GRAMMAR_REVISION));
+    }
+}
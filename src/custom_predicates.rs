@@ -1,4 +1,4 @@
-use anyhow::{Context, Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use tree_sitter::{Node, QueryCapture, QueryPredicate, QueryPredicateArg};
@@ -70,19 +70,36 @@ fn subtree_depth(node: Node, types: &HashSet<&str>) -> usize {
     max
 }
 
+/// The arguments of `#ancestor-depth?` and `#subtree-depth?`: `@capture "type,..." "threshold"`
+struct DepthArgs<'q> {
+    capture_idx: u32,
+    types: &'q str,
+    threshold: usize,
+}
+
+fn parse_depth_args(pred: &QueryPredicate) -> Result<DepthArgs<'_>> {
+    Ok(DepthArgs {
+        capture_idx: resolve_capture_idx(&pred.args, 0)?,
+        types: resolve_string_arg(&pred.args, 1)?,
+        threshold: resolve_string_arg(&pred.args, 2)?
+            .parse()
+            .with_context(|| format!("{} threshold must be a number", pred.operator.as_ref()))?,
+    })
+}
+
 fn eval_depth_predicate<'q>(
     pred: &'q QueryPredicate,
     captures: &[QueryCapture<'_>],
     types_cache: &mut HashMap<&'q str, HashSet<&'q str>>,
     depth_fn: fn(Node, &HashSet<&str>) -> usize,
 ) -> Result<bool> {
-    let capture_idx = resolve_capture_idx(&pred.args, 0)?;
+    let DepthArgs {
+        capture_idx,
+        types: types_str,
+        threshold,
+    } = parse_depth_args(pred)?;
     let node = find_capture_node(captures, capture_idx)
         .ok_or_else(|| anyhow::anyhow!("capture not found in match"))?;
-    let types_str = resolve_string_arg(&pred.args, 1)?;
-    let threshold: usize = resolve_string_arg(&pred.args, 2)?
-        .parse()
-        .with_context(|| format!("{} threshold must be a number", pred.operator.as_ref()))?;
     let types = match types_cache.entry(types_str) {
         Entry::Occupied(e) => e.into_mut(),
         Entry::Vacant(e) => e.insert(types_str.split(',').map(str::trim).collect()),
@@ -90,6 +107,23 @@ fn eval_depth_predicate<'q>(
     Ok(depth_fn(node, types) >= threshold)
 }
 
+/// Checks the arguments of all custom predicates, so a broken rule fails when it's compiled rather than on the
+/// first match
+pub fn validate(query: &tree_sitter::Query) -> Result<()> {
+    for pattern in 0..query.pattern_count() {
+        for pred in query.general_predicates(pattern) {
+            let op = pred.operator.as_ref();
+            match op {
+                "ancestor-depth?" | "subtree-depth?" => {
+                    parse_depth_args(pred).with_context(|| format!("in #{op}"))?;
+                }
+                unknown => bail!("Unknown custom predicate: #{unknown}"),
+            }
+        }
+    }
+    Ok(())
+}
+
 fn evaluate_predicates<'q>(
     predicates: &'q [QueryPredicate],
     captures: &[QueryCapture<'_>],
@@ -122,25 +156,43 @@ fn is_trailing(m: &tree_sitter::QueryMatch, idx: u32) -> bool {
         .any(|n| n.next_named_sibling().is_some())
 }
 
+/// Indices of the captures with a special meaning, looked up once when a rule is compiled
+#[derive(Debug, Clone, Copy)]
+pub struct SpecialCaptures {
+    pub error: u32,
+    pub trailing: Option<u32>,
+    pub filter: Option<u32>,
+}
+
+impl SpecialCaptures {
+    pub fn new(query: &tree_sitter::Query) -> Result<Self> {
+        Ok(Self {
+            error: query
+                .capture_index_for_name("error")
+                .ok_or_else(|| anyhow!("Expected query to contain `@error` captures"))?,
+            trailing: query.capture_index_for_name("trailing"),
+            filter: query.capture_index_for_name("filter"),
+        })
+    }
+}
+
 pub struct MatchEvaluator<'q> {
     query: &'q tree_sitter::Query,
-    trailing_idx: Option<u32>,
-    filter_idx: Option<u32>,
+    captures: SpecialCaptures,
     types_cache: HashMap<&'q str, HashSet<&'q str>>,
 }
 
 impl<'q> MatchEvaluator<'q> {
-    pub fn new(query: &'q tree_sitter::Query) -> Self {
+    pub fn new(query: &'q tree_sitter::Query, captures: SpecialCaptures) -> Self {
         Self {
-            trailing_idx: query.capture_index_for_name("trailing"),
-            filter_idx: query.capture_index_for_name("filter"),
-            types_cache: HashMap::new(),
             query,
+            captures,
+            types_cache: HashMap::new(),
         }
     }
 
     pub fn should_skip(&mut self, m: &tree_sitter::QueryMatch) -> Result<bool> {
-        if let Some(idx) = self.trailing_idx
+        if let Some(idx) = self.captures.trailing
             && is_trailing(m, idx)
         {
             return Ok(true);
@@ -159,7 +211,7 @@ impl<'q> MatchEvaluator<'q> {
         m: &tree_sitter::QueryMatch,
         out: &mut HashSet<tree_sitter::Range>,
     ) {
-        if let Some(idx) = self.filter_idx {
+        if let Some(idx) = self.captures.filter {
             for node in m.nodes_for_capture_index(idx) {
                 out.insert(node.range());
             }
@@ -181,6 +233,7 @@ mod test {
             severity: Default::default(),
             includes: vec![],
             excludes: vec![],
+            escalate: None,
         };
         let res = lint_file(&Config::default(), "<test>", input, &[rule], &mut out).unwrap();
         assert_eq!(res.error_count, expected);
@@ -196,10 +249,11 @@ mod test {
             severity: Default::default(),
             includes: vec![],
             excludes: vec![],
+            escalate: None,
         };
         let res = lint_file(&Config::default(), "<test>", input, &[rule], &mut out);
-        assert!(res.is_err());
-        assert!(res.unwrap_err().to_string().contains(expected_err));
+        let message = res.unwrap_err().to_string();
+        assert!(message.contains(expected_err), "{message}");
     }
 
     #[test]
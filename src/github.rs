@@ -0,0 +1,89 @@
+use crate::{Diagnostic, RuleSeverity, char_column};
+
+// See https://docs.github.com/en/actions/reference/workflows-and-actions/workflow-commands
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Formats a diagnostic as a workflow command, which GitHub Actions turns into an inline annotation
+pub(crate) fn print_diagnostic(path: &str, source_code: &str, diagnostic: &Diagnostic) -> String {
+    let command = match diagnostic.severity {
+        RuleSeverity::Off | RuleSeverity::Hint | RuleSeverity::Info => "notice",
+        RuleSeverity::Warning => "warning",
+        RuleSeverity::Error => "error",
+    };
+    let range = diagnostic.range;
+    format!(
+        "::{command} file={file},line={line},col={col},endLine={end_line},endColumn={end_col},title={title}::{message}",
+        file = escape_property(path),
+        line = range.start_point.row + 1,
+        col = char_column(source_code, range.start_byte, range.start_point),
+        end_line = range.end_point.row + 1,
+        end_col = char_column(source_code, range.end_byte, range.end_point),
+        title = escape_property(&diagnostic.rule),
+        message = escape_data(&diagnostic.description),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Config, OutputFormat, Rule, lint_file, test_rule};
+
+    #[test]
+    fn escapes_workflow_command_values() {
+        assert_eq!(escape_data("100%\nnext\r\n"), "100%25%0Anext%0D%0A");
+        assert_eq!(escape_property("a:b,c%"), "a%3Ab%2Cc%25");
+    }
+
+    #[test]
+    fn findings_that_are_off_are_notices() {
+        let diagnostic = Diagnostic {
+            rule: "r".into(),
+            description: "d".into(),
+            range: tree_sitter::Range {
+                start_byte: 0,
+                end_byte: 1,
+                start_point: tree_sitter::Point::new(0, 0),
+                end_point: tree_sitter::Point::new(0, 1),
+            },
+            fix: None,
+            severity: RuleSeverity::Off,
+            context: String::new(),
+            escalation: None,
+        };
+        assert!(print_diagnostic("a.mo", "x", &diagnostic).starts_with("::notice "));
+    }
+
+    #[test]
+    fn it_prints_workflow_commands() {
+        let mut out: Vec<u8> = vec![];
+        let rule = Rule {
+            name: "no-let-else".into(),
+            description: "Avoid `@error`\nUse a switch: 100%".into(),
+            severity: RuleSeverity::Warning,
+            ..test_rule("(let_else_dec) @error")
+        };
+        lint_file(
+            &Config {
+                format: OutputFormat::Github,
+                ..Config::default()
+            },
+            "src/a,b.mo",
+            "let ?x = y else { return }",
+            &[rule],
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            str::from_utf8(&out).unwrap(),
+            "::warning file=src/a%2Cb.mo,line=1,col=1,endLine=1,endColumn=27,title=no-let-else::Avoid `let ?x = y else { return }`%0AUse a switch: 100%25\n"
+        );
+    }
+}
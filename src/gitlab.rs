@@ -0,0 +1,123 @@
+use crate::RuleSeverity;
+use crate::report::Report;
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+
+#[derive(Serialize)]
+struct Lines {
+    begin: usize,
+}
+
+#[derive(Serialize)]
+struct Location<'a> {
+    path: &'a str,
+    lines: Lines,
+}
+
+#[derive(Serialize)]
+struct Issue<'a> {
+    description: &'a str,
+    check_name: &'a str,
+    fingerprint: String,
+    severity: &'static str,
+    location: Location<'a>,
+}
+
+fn severity(severity: RuleSeverity) -> &'static str {
+    match severity {
+        RuleSeverity::Off | RuleSeverity::Hint | RuleSeverity::Info => "info",
+        RuleSeverity::Warning => "minor",
+        RuleSeverity::Error => "major",
+    }
+}
+
+/// Identifies a finding by what was matched instead of where, so that it stays the same when unrelated
+/// lines are added or removed above it. `occurrence` tells apart identical matches within the same file.
+fn fingerprint(path: &str, rule: &str, matched: &str, occurrence: usize) -> String {
+    let mut hasher = Sha256::new();
+    for part in [path, rule, matched, &occurrence.to_string()] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Writes the report as a GitLab Code Quality report
+pub(crate) fn write(report: &Report, mut out: impl Write) -> Result<()> {
+    let mut issues = vec![];
+    for file in &report.files {
+        let mut occurrences: HashMap<(&str, &str), usize> = HashMap::new();
+        for d in &file.diagnostics {
+            let matched = file
+                .source
+                .get(d.range.start_byte..d.range.end_byte)
+                .unwrap_or_default();
+            let occurrence = occurrences.entry((&d.rule, matched)).or_default();
+            issues.push(Issue {
+                description: &d.description,
+                check_name: &d.rule,
+                fingerprint: fingerprint(&file.path, &d.rule, matched, *occurrence),
+                severity: severity(d.severity),
+                location: Location {
+                    path: &file.path,
+                    lines: Lines {
+                        begin: d.range.start_point.row + 1,
+                    },
+                },
+            });
+            *occurrence += 1;
+        }
+    }
+    serde_json::to_writer_pretty(&mut out, &issues)?;
+    writeln!(&mut out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::report::test::{pun_fields, render};
+    use crate::{Config, OutputFormat};
+
+    fn issues(source: &str) -> Vec<serde_json::Value> {
+        let config = Config {
+            format: OutputFormat::Gitlab,
+            ..Config::default()
+        };
+        let out = render(&config, &pun_fields(), &[("main.mo", source)]);
+        serde_json::from_slice::<serde_json::Value>(&out)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn it_writes_code_quality_issues() {
+        let issues = issues("{ x = x }");
+        assert_eq!(issues.len(), 1);
+        let issue = &issues[0];
+        assert_eq!(issue["check_name"], "pun-fields");
+        assert_eq!(issue["severity"], "minor");
+        assert_eq!(issue["location"]["path"], "main.mo");
+        assert_eq!(issue["location"]["lines"]["begin"], 1);
+    }
+
+    #[test]
+    fn fingerprints_survive_unrelated_edits() {
+        let before = issues("{ x = x; y = y; x = x }");
+        let after = issues("// new line\n\n{ x = x; y = y; x = x }");
+        assert_eq!(after[0]["location"]["lines"]["begin"], 3);
+        let fingerprints = |issues: &[serde_json::Value]| {
+            issues
+                .iter()
+                .map(|i| i["fingerprint"].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fingerprints(&before), fingerprints(&after));
+        // Repeated identical findings must still be told apart
+        assert_ne!(before[0]["fingerprint"], before[2]["fingerprint"]);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::Diagnostic;
+use crate::report::Report;
+use anyhow::Result;
+use std::io::Write;
+use std::ops::Range;
+
+// Everything is inlined, so the report works offline and can be archived as a single file
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #1f2328; }
+table { border-collapse: collapse; margin-bottom: 1rem; }
+th, td { border: 1px solid #d0d7de; padding: 0.25rem 0.75rem; text-align: left; }
+pre { background: #f6f8fa; padding: 0.5rem; overflow-x: auto; }
+mark { background: #ffd8b5; }
+.ln { color: #8c959f; user-select: none; display: inline-block; min-width: 3ch; text-align: right; margin-right: 1ch; }
+.error { color: #cf222e; }
+.warning { color: #9a6700; }
+.info, .hint { color: #0969da; }
+.fix { background: #dafbe1; }
+"#;
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Byte ranges of the lines that the span touches
+fn lines_of(source: &str, span: Range<usize>) -> Range<usize> {
+    let start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = source[span.end..]
+        .find('\n')
+        .map_or(source.len(), |i| span.end + i);
+    start..end
+}
+
+/// Renders the lines touched by the diagnostic, with line numbers and the span itself highlighted
+fn excerpt(source: &str, diagnostic: &Diagnostic) -> String {
+    let span = diagnostic.range.start_byte..diagnostic.range.end_byte;
+    let lines = lines_of(source, span.clone());
+    let mut html = String::new();
+    let mut line_start = lines.start;
+    for (i, line) in source[lines].split('\n').enumerate() {
+        let line_end = line_start + line.len();
+        let mark_start = span.start.clamp(line_start, line_end);
+        let mark_end = span.end.clamp(line_start, line_end);
+        html += &format!(
+            "<span class=\"ln\">{}</span>{}<mark>{}</mark>{}\n",
+            diagnostic.range.start_point.row + 1 + i,
+            escape(&source[line_start..mark_start]),
+            escape(&source[mark_start..mark_end]),
+            escape(&source[mark_end..line_end]),
+        );
+        line_start = line_end + 1;
+    }
+    html
+}
+
+/// Renders the touched lines as they would look after applying the fix
+fn fix_preview(source: &str, diagnostic: &Diagnostic, fix: &str) -> String {
+    let span = diagnostic.range.start_byte..diagnostic.range.end_byte;
+    let lines = lines_of(source, span.clone());
+    let fixed = format!(
+        "{}{fix}{}",
+        &source[lines.start..span.start],
+        &source[span.end..lines.end]
+    );
+    escape(&fixed)
+}
+
+/// Writes the report as a single static HTML page
+pub(crate) fn write(report: &Report, mut out: impl Write) -> Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"en\">")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>lintoko report</title>")?;
+    writeln!(out, "<style>{STYLE}</style>")?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>lintoko report</h1>")?;
+
+    writeln!(out, "<h2>Summary</h2>")?;
+    writeln!(out, "<table>")?;
+    writeln!(out, "<tr><th>Severity</th><th>Count</th></tr>")?;
+    writeln!(
+        out,
+        "<tr><td class=\"error\">error</td><td>{}</td></tr>",
+        report.error_count
+    )?;
+    writeln!(
+        out,
+        "<tr><td class=\"warning\">warning</td><td>{}</td></tr>",
+        report.warning_count
+    )?;
+    for (severity, count) in [("info", report.info_count), ("hint", report.hint_count)] {
+        if count > 0 {
+            writeln!(
+                out,
+                "<tr><td class=\"{severity}\">{severity}</td><td>{count}</td></tr>"
+            )?;
+        }
+    }
+    writeln!(out, "</table>")?;
+    writeln!(out, "<table>")?;
+    writeln!(out, "<tr><th>Rule</th><th>Severity</th><th>Count</th></tr>")?;
+    for ((rule, severity), count) in report.per_rule() {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td class=\"{severity}\">{severity}</td><td>{count}</td></tr>",
+            escape(rule)
+        )?;
+    }
+    writeln!(out, "</table>")?;
+    writeln!(out, "<p>Linted {} files.</p>", report.files.len())?;
+
+    for file in report.files.iter().filter(|f| !f.diagnostics.is_empty()) {
+        writeln!(out, "<h2>{}</h2>", escape(&file.path))?;
+        for d in &file.diagnostics {
+            let severity = d.severity;
+            writeln!(out, "<section>")?;
+            writeln!(
+                out,
+                "<h3><span class=\"{severity}\">{severity}</span> {} <small>{}:{}</small></h3>",
+                escape(&d.rule),
+                d.range.start_point.row + 1,
+                d.range.start_point.column + 1,
+            )?;
+            writeln!(out, "<p>{}</p>", escape(&d.description))?;
+            writeln!(out, "<pre>{}</pre>", excerpt(&file.source, d))?;
+            if let Some(fix) = &d.fix {
+                writeln!(out, "<p>Fix:</p>")?;
+                writeln!(
+                    out,
+                    "<pre class=\"fix\">{}</pre>",
+                    fix_preview(&file.source, d, fix)
+                )?;
+            }
+            writeln!(out, "</section>")?;
+        }
+    }
+
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::report::test::{pun_fields, render};
+    use crate::{Config, OutputFormat, RuleSeverity};
+
+    #[test]
+    fn it_writes_a_self_contained_page() {
+        let config = Config {
+            format: OutputFormat::Html,
+            ..Config::default()
+        };
+        let source = "let a = \"<b>\";\nlet r = { x = x };";
+        let out = render(&config, &pun_fields(), &[("main.mo", source)]);
+        let html = str::from_utf8(&out).unwrap();
+
+        assert!(!html.contains("<link"), "must not load external assets");
+        assert!(!html.contains("<script"), "must not load external assets");
+        assert!(
+            html.contains(
+                "<tr><td>pun-fields</td><td class=\"warning\">warning</td><td>1</td></tr>"
+            ),
+            "{html}"
+        );
+        assert!(
+            html.contains("<span class=\"ln\">2</span>let r = { <mark>x = x</mark> };"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<pre class=\"fix\">let r = { x };</pre>"),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn excerpts_highlight_multi_line_spans() {
+        let source = "func f() {\n  x < y\n}";
+        let diagnostic = Diagnostic {
+            rule: "blocks".into(),
+            description: "test".into(),
+            range: tree_sitter::Range {
+                start_byte: 9,
+                end_byte: source.len(),
+                start_point: tree_sitter::Point::new(0, 9),
+                end_point: tree_sitter::Point::new(2, 1),
+            },
+            fix: None,
+            severity: RuleSeverity::Error,
+            context: String::new(),
+            escalation: None,
+        };
+        assert_eq!(
+            excerpt(source, &diagnostic),
+            "<span class=\"ln\">1</span>func f() <mark>{</mark>\n\
+             <span class=\"ln\">2</span><mark>  x &lt; y</mark>\n\
+             <span class=\"ln\">3</span><mark>}</mark>\n"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use crate::report::Report;
+use crate::{Diagnostic, RuleSeverity};
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+use tree_sitter::Point;
+
+/// Bumped whenever the shape of the JSON output changes incompatibly. See the README for the schema.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+struct Position {
+    byte: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    // Lines and columns are 1-based. Columns count bytes, just like tree-sitter does.
+    fn new(byte: usize, point: Point) -> Self {
+        Self {
+            byte,
+            line: point.row + 1,
+            column: point.column + 1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonDiagnostic {
+    path: String,
+    rule: String,
+    severity: RuleSeverity,
+    description: String,
+    start: Position,
+    end: Position,
+    fix: Option<String>,
+}
+
+impl JsonDiagnostic {
+    fn new(path: &str, diagnostic: &Diagnostic) -> Self {
+        let range = diagnostic.range;
+        Self {
+            path: path.to_string(),
+            rule: diagnostic.rule.clone(),
+            severity: diagnostic.severity,
+            description: diagnostic.description.clone(),
+            start: Position::new(range.start_byte, range.start_point),
+            end: Position::new(range.end_byte, range.end_point),
+            fix: diagnostic.fix.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    errors: usize,
+    warnings: usize,
+    infos: usize,
+    hints: usize,
+}
+
+#[derive(Serialize)]
+struct Document {
+    version: u32,
+    diagnostics: Vec<JsonDiagnostic>,
+    summary: Summary,
+}
+
+pub(crate) fn write(report: &Report, mut out: impl Write) -> Result<()> {
+    let diagnostics = report
+        .files
+        .iter()
+        .flat_map(|file| {
+            file.diagnostics
+                .iter()
+                .map(|d| JsonDiagnostic::new(&file.path, d))
+        })
+        .collect();
+    let document = Document {
+        version: JSON_SCHEMA_VERSION,
+        diagnostics,
+        summary: Summary {
+            errors: report.error_count,
+            warnings: report.warning_count,
+            infos: report.info_count,
+            hints: report.hint_count,
+        },
+    };
+    serde_json::to_writer_pretty(&mut out, &document)?;
+    writeln!(&mut out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::report::test::{pun_fields, render};
+    use crate::{Config, OutputFormat};
+
+    #[test]
+    fn it_writes_a_single_document() {
+        let config = Config {
+            format: OutputFormat::Json,
+            ..Config::default()
+        };
+        let json = render(
+            &config,
+            &pun_fields(),
+            &[("<input_path>", "{ x = x }"), ("<empty>", "")],
+        );
+
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["version"], JSON_SCHEMA_VERSION);
+        assert_eq!(value["summary"]["errors"], 0);
+        assert_eq!(value["summary"]["warnings"], 1);
+        let diagnostics = value["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        let d = &diagnostics[0];
+        assert_eq!(d["path"], "<input_path>");
+        assert_eq!(d["rule"], "pun-fields");
+        assert_eq!(d["severity"], "warning");
+        assert_eq!(d["start"]["byte"], 2);
+        assert_eq!(d["start"]["line"], 1);
+        assert_eq!(d["start"]["column"], 3);
+        assert_eq!(d["end"]["byte"], 7);
+        assert_eq!(d["end"]["column"], 8);
+        assert_eq!(d["fix"], "x");
+    }
+}
@@ -1,38 +1,133 @@
+mod baseline;
+mod cache;
 mod custom_predicates;
-
-use anyhow::{Context, Result, anyhow};
+mod github;
+mod gitlab;
+mod html;
+mod json;
+mod linter;
+mod markdown;
+mod project;
+mod render;
+mod report;
+mod sarif;
+mod selection;
+mod suppression;
+mod xml;
+
+pub use baseline::{BASELINE_VERSION, Baseline, BaselineEntry};
+pub use cache::{CACHE_DIR, Cache};
+pub use linter::{Linter, ParsedFile, Strategy};
+pub use project::{
+    ConfigResolver, PROJECT_CONFIG_FILE, ProjectConfig, ResolvedConfig, RuleOverride,
+};
+pub use render::{
+    CompactRenderer, GithubRenderer, PrettyRenderer, Renderer, Reporter, TextRenderer, renderer,
+};
+pub use selection::{RuleSelection, did_you_mean};
+pub use suppression::{Suppression, SuppressionKind};
+pub use tree_sitter::{Point, Range};
+
+use anyhow::{Context, Result, anyhow, bail};
 use glob::Pattern;
-use miette::{LabeledSpan, NamedSource, Severity, miette};
+use miette::{LabeledSpan, MietteDiagnostic, NamedSource, Severity};
 use regex::Regex;
-use serde::{Deserialize, Deserializer};
-use std::collections::HashSet;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::{fs, io::Write, path::Path};
 use tracing::debug;
-use tree_sitter::{Node, Parser, Query, QueryCapture, QueryCursor, Range, StreamingIterator};
+use tree_sitter::{Node, Parser, Query, QueryCapture};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
     #[default]
     Pretty,
     Text,
+    Compact,
+    Github,
+    Json,
+    Sarif,
+    Checkstyle,
+    Junit,
+    Gitlab,
+    Html,
+    Markdown,
+}
+
+impl OutputFormat {
+    /// Whether the format describes the whole run in a single document, see `Report`
+    pub fn is_aggregated(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Json
+                | OutputFormat::Sarif
+                | OutputFormat::Checkstyle
+                | OutputFormat::Junit
+                | OutputFormat::Gitlab
+                | OutputFormat::Html
+                | OutputFormat::Markdown
+        )
+    }
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum RuleSeverity {
+    /// The rule stays loaded, e.g. for listing it, but is never run
+    Off,
+    Hint,
+    Info,
     Warning,
     #[default]
     Error,
 }
 
+impl std::fmt::Display for RuleSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RuleSeverity::Off => "off",
+            RuleSeverity::Hint => "hint",
+            RuleSeverity::Info => "info",
+            RuleSeverity::Warning => "warning",
+            RuleSeverity::Error => "error",
+        };
+        f.write_str(name)
+    }
+}
+
+impl RuleSeverity {
+    /// Hints and infos are rendered, but don't count as warnings and never fail a run
+    pub fn is_advisory(self) -> bool {
+        matches!(self, RuleSeverity::Hint | RuleSeverity::Info)
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Config {
     pub format: OutputFormat,
     pub fix: bool,
+    /// Replaces the severity of all findings. `Off` turns off all rules, like it does for a single rule
     pub severity_override: Option<RuleSeverity>,
+    /// Also print where a diagnostic ends, for the `Compact` format
+    pub end_positions: bool,
+    /// Limits how many findings the `Markdown` format lists, to stay within comment size limits
+    pub max_findings: Option<usize>,
+    /// Also reports unused, reasonless, and unknown-rule suppression comments as diagnostics. Invalid
+    /// and expired ones are always reported
+    pub audit_suppressions: bool,
+    /// The date suppression expiry and rule escalations are checked against. Defaults to the current
+    /// local date
+    pub today: Option<jiff::civil::Date>,
+}
+
+impl Config {
+    /// `today`, or the current local date if it isn't set
+    pub fn today(&self) -> jiff::civil::Date {
+        self.today.unwrap_or_else(|| jiff::Zoned::now().date())
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Rule {
     name: String,
     description: String,
@@ -46,9 +141,31 @@ pub struct Rule {
     // Path globs the rule is skipped on; takes precedence over `includes`.
     #[serde(default, deserialize_with = "deserialize_globs")]
     excludes: Vec<Pattern>,
+    escalate: Option<Escalation>,
+}
+
+/// Switches a rule to a different severity once a date has passed, to phase in new rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Escalation {
+    pub to: RuleSeverity,
+    #[serde(deserialize_with = "deserialize_date")]
+    pub after: jiff::civil::Date,
+}
+
+impl Escalation {
+    pub fn has_passed(&self, today: jiff::civil::Date) -> bool {
+        today > self.after
+    }
+}
+
+fn deserialize_date<'de, D: Deserializer<'de>>(d: D) -> Result<jiff::civil::Date, D::Error> {
+    let date = String::deserialize(d)?;
+    date.parse()
+        .map_err(|e| serde::de::Error::custom(format!("invalid date {date:?}: {e}")))
 }
 
-fn deserialize_globs<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Pattern>, D::Error> {
+pub(crate) fn deserialize_globs<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Pattern>, D::Error> {
     Vec::<String>::deserialize(d)?
         .into_iter()
         .map(|p| {
@@ -59,6 +176,37 @@ fn deserialize_globs<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Pattern>, D:
 }
 
 impl Rule {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn severity(&self) -> RuleSeverity {
+        self.severity
+    }
+
+    /// The configured escalation, if any. Severities set in `lintoko.toml` or on the command line remove it
+    pub fn escalation(&self) -> Option<Escalation> {
+        self.escalate
+    }
+
+    /// The severity of the rule on `today`, escalated if the date of its escalation has passed
+    pub fn severity_on(&self, today: jiff::civil::Date) -> RuleSeverity {
+        match self.escalate {
+            Some(escalation) if escalation.has_passed(today) => escalation.to,
+            _ => self.severity,
+        }
+    }
+
+    /// Sets a severity that doesn't escalate anymore
+    pub(crate) fn pin_severity(&mut self, severity: RuleSeverity) {
+        self.severity = severity;
+        self.escalate = None;
+    }
+
     fn applies_to(&self, path: &str) -> bool {
         let matches_any = |pats: &[Pattern]| pats.iter().any(|p| p.matches(path));
         (self.includes.is_empty() || matches_any(&self.includes)) && !matches_any(&self.excludes)
@@ -66,12 +214,29 @@ impl Rule {
 }
 
 #[derive(Debug, Clone)]
-struct RawDiagnostic {
-    rule: String,
-    description: String,
-    range: Range,
-    fix: Option<String>,
-    severity: RuleSeverity,
+pub struct Diagnostic {
+    pub rule: String,
+    pub description: String,
+    pub range: Range,
+    /// Replacement text for `range`, with captures already templated in
+    pub fix: Option<String>,
+    pub severity: RuleSeverity,
+    /// Where in the syntax tree the finding is, see `structural_context`. Identifies it across unrelated edits
+    pub context: String,
+    /// The upcoming escalation of the rule, see `Rule::escalation`
+    pub escalation: Option<Escalation>,
+}
+
+/// A new empty directory under the temp dir. Every call gets its own, so tests running in parallel don't collide
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("lintoko-{name}-{}-{n}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
 }
 
 #[cfg(test)]
@@ -84,6 +249,7 @@ pub(crate) fn test_rule(query: &str) -> Rule {
         severity: RuleSeverity::default(),
         includes: vec![],
         excludes: vec![],
+        escalate: None,
     }
 }
 
@@ -138,82 +304,61 @@ fn template(
     Ok(new)
 }
 
-fn apply_rule(rule: &Rule, tree: Node, input: &str) -> Result<Vec<RawDiagnostic>> {
-    let query = Query::new(&tree_sitter_motoko::LANGUAGE.into(), &rule.query)
-        .with_context(|| format!("Failed to create query for rule '{}'", rule.name))?;
-    let error_capture_index = query.capture_index_for_name("error").with_context(|| {
-        anyhow!(
-            "Expected query to contain `@error` captures:\n{}",
-            rule.query
-        )
-    })?;
-    let mut evaluator = custom_predicates::MatchEvaluator::new(&query);
-    let mut cursor = QueryCursor::new();
-    let mut matches = cursor.matches(&query, tree, input.as_bytes());
-    let mut filtered: HashSet<Range> = HashSet::new();
-    let mut errors = Vec::new();
-    while let Some(m) = matches.next() {
-        if evaluator.should_skip(m)? {
-            continue;
-        }
-        for error_node in m.nodes_for_capture_index(error_capture_index) {
-            // NOTE: We have to use `to_vec` here, or tree-sitter will silently swap the captures under our feet.
-            errors.push((error_node.range(), m.captures.to_vec()));
-        }
-        evaluator.collect_filter_ranges(m, &mut filtered);
+/// The kinds of the node and all its ancestors, e.g. `source_file/dec_field/let_dec/obj_exp`
+fn structural_context(node: Node) -> String {
+    let mut kinds = vec![node.kind()];
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        kinds.push(parent.kind());
+        current = parent.parent();
     }
-    let mut seen = HashSet::new();
-    let mut diagnostics = vec![];
-    for (range, captures) in errors {
-        if filtered.contains(&range) {
-            continue;
-        }
-        // Avoid reporting the same diagnostic twice on the same range
-        if !seen.insert(range) {
-            continue;
-        }
-        let description = template(&rule.description, &query, &captures, input)?;
-        let fix = if let Some(ref fix_template) = rule.fix {
-            Some(template(fix_template, &query, &captures, input)?)
-        } else {
-            None
-        };
+    kinds.reverse();
+    kinds.join("/")
+}
 
-        let diagnostic = RawDiagnostic {
-            rule: rule.name.to_string(),
-            description,
-            range,
-            fix,
-            severity: rule.severity,
-        };
-        diagnostics.push(diagnostic);
-    }
-    Ok(diagnostics)
+/// 1-based column of `point`, counted in characters instead of bytes
+pub(crate) fn char_column(source_code: &str, byte: usize, point: Point) -> usize {
+    source_code
+        .get(byte - point.column..byte)
+        .map_or(point.column, |line| line.chars().count())
+        + 1
 }
 
-fn print_pretty_diagnostic(path: &str, source_code: &str, diagnostic: &RawDiagnostic) -> String {
+pub(crate) fn print_pretty_diagnostic(
+    path: &str,
+    source_code: &str,
+    diagnostic: &Diagnostic,
+) -> String {
     let source_code = NamedSource::new(path, source_code.to_string());
-    let (miette_severity, label) = match diagnostic.severity {
-        RuleSeverity::Warning => (Severity::Warning, "[WARNING]"),
-        RuleSeverity::Error => (Severity::Error, "[ERROR]"),
+    let miette_severity = match diagnostic.severity {
+        RuleSeverity::Off | RuleSeverity::Hint | RuleSeverity::Info => Severity::Advice,
+        RuleSeverity::Warning => Severity::Warning,
+        RuleSeverity::Error => Severity::Error,
     };
-    let report = miette!(
-        severity = miette_severity,
-        labels = vec![LabeledSpan::new_primary_with_span(
+    let label = diagnostic.severity.to_string().to_uppercase();
+    let mut report = MietteDiagnostic::new(format!("[{label}]: {}", diagnostic.rule))
+        .with_severity(miette_severity)
+        .with_label(LabeledSpan::new_primary_with_span(
             Some(diagnostic.description.clone()),
             (
                 diagnostic.range.start_byte,
-                diagnostic.range.end_byte - diagnostic.range.start_byte
-            )
-        )],
-        "{label}: {}",
-        diagnostic.rule
-    )
-    .with_source_code(source_code);
+                diagnostic.range.end_byte - diagnostic.range.start_byte,
+            ),
+        ));
+    if let Some(escalation) = diagnostic.escalation
+        && diagnostic.severity < escalation.to
+    {
+        report = report.with_help(format!(
+            "This rule escalates to `{}` after {}",
+            escalation.to, escalation.after
+        ));
+    }
+    let report = miette::Report::new(report).with_source_code(source_code);
     format!("{report:?}")
 }
 
-fn print_text_diagnostic(path: &str, source_code: &str, diagnostic: &RawDiagnostic) -> String {
+/// The source lines covered by the diagnostic, prefixed with their line numbers
+pub(crate) fn snippet(source_code: &str, diagnostic: &Diagnostic) -> String {
     let mut snippet = String::new();
     let start_line = diagnostic.range.start_point.row + 1;
     let end_line = diagnostic.range.end_point.row + 1;
@@ -228,11 +373,18 @@ fn print_text_diagnostic(path: &str, source_code: &str, diagnostic: &RawDiagnost
         let padding = " ".repeat((max_line_chars - line_chars) as usize);
         snippet += &format!("{padding}{l} {line}\n");
     }
+    snippet
+}
 
-    let severity_label = match diagnostic.severity {
-        RuleSeverity::Warning => "Warning",
-        RuleSeverity::Error => "Error",
-    };
+pub(crate) fn print_text_diagnostic(
+    path: &str,
+    source_code: &str,
+    diagnostic: &Diagnostic,
+) -> String {
+    let snippet = snippet(source_code, diagnostic);
+    let severity = diagnostic.severity.to_string();
+    let severity_label = severity[..1].to_uppercase() + &severity[1..];
+    let start_line = diagnostic.range.start_point.row + 1;
     let start = format!("{start_line}:{}", diagnostic.range.start_point.column);
     format!(
         "{path}:{start} {severity_label}: {description}\nFound in:\n{snippet}",
@@ -240,13 +392,104 @@ fn print_text_diagnostic(path: &str, source_code: &str, diagnostic: &RawDiagnost
     )
 }
 
-#[derive(Debug)]
+/// Prints a diagnostic on a single line, in a shape that quickfix lists and `errorformat` parsers understand.
+/// Lines and columns are 1-based, and columns count bytes.
+pub(crate) fn print_compact_diagnostic(
+    path: &str,
+    diagnostic: &Diagnostic,
+    end_positions: bool,
+) -> String {
+    let range = diagnostic.range;
+    let mut position = format!(
+        "{}:{}",
+        range.start_point.row + 1,
+        range.start_point.column + 1
+    );
+    if end_positions {
+        position += &format!(
+            ":{}:{}",
+            range.end_point.row + 1,
+            range.end_point.column + 1
+        );
+    }
+    let description = diagnostic.description.lines().collect::<Vec<_>>().join(" ");
+    format!(
+        "{path}:{position}: {}: {description} [{}]",
+        diagnostic.severity, diagnostic.rule
+    )
+}
+
+pub const FIX_OVERLAPS_MESSAGE: &str =
+    "Spotted overlaps when applying fixes. Re-run the command to make progress";
+
+#[derive(Debug, Default)]
 pub struct LintResult {
     pub error_count: usize,
     pub warning_count: usize,
+    pub info_count: usize,
+    pub hint_count: usize,
     pub fixed_file: Option<String>,
+    /// Some fixes were skipped because they overlapped, see `FIX_OVERLAPS_MESSAGE`
+    pub fix_overlaps: bool,
+    /// All diagnostics for the file, sorted by their start position
+    pub diagnostics: Vec<Diagnostic>,
+    /// Rules that failed on the file. The other rules still ran
+    pub tool_diagnostics: Vec<ToolDiagnostic>,
+}
+
+/// A failure of lintoko itself rather than a finding, e.g. a rule with an invalid query or an unreadable file.
+/// They don't stop the rest of the run, so one broken rule doesn't hide the results of all others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolDiagnostic {
+    /// The file that failed, or `None` for rules that failed to compile
+    pub path: Option<String>,
+    /// The rule that failed, or `None` if the whole file couldn't be linted
+    pub rule: Option<String>,
+    pub message: String,
 }
 
+impl LintResult {
+    /// Recomputes the per-severity counts from `diagnostics`
+    pub(crate) fn count_severities(&mut self) {
+        self.error_count = 0;
+        self.warning_count = 0;
+        self.info_count = 0;
+        self.hint_count = 0;
+        for d in &self.diagnostics {
+            match d.severity {
+                RuleSeverity::Error => self.error_count += 1,
+                RuleSeverity::Warning => self.warning_count += 1,
+                RuleSeverity::Info => self.info_count += 1,
+                RuleSeverity::Hint => self.hint_count += 1,
+                RuleSeverity::Off => {}
+            }
+        }
+    }
+}
+
+/// Lists the suppression comments in a file, in source order
+pub fn suppressions(input: &str) -> Vec<Suppression> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_motoko::LANGUAGE.into())
+        .expect("Error loading Motoko grammar");
+    let tree = parser.parse(input.as_bytes(), None).unwrap();
+    suppression::collect(tree.root_node(), input)
+}
+
+/// Lints a single file without printing anything. Use a `Reporter` to render the result, and a `Linter` to lint
+/// many files with the same rules
+pub fn lint(config: &Config, path: &str, input: &str, rules: &[Rule]) -> Result<LintResult> {
+    let mut linter = Linter::new(rules);
+    let mut result = linter.lint(config, path, input)?;
+    result
+        .tool_diagnostics
+        .extend(linter.rule_errors().iter().cloned());
+    Ok(result)
+}
+
+/// Lints a single file and prints its diagnostics in `config.format`. Fails if any rule failed, unlike
+/// `lint`, which reports failing rules in `LintResult::tool_diagnostics`
 pub fn lint_file(
     config: &Config,
     path: &str,
@@ -254,78 +497,27 @@ pub fn lint_file(
     rules: &[Rule],
     mut out: impl Write,
 ) -> Result<LintResult> {
-    let mut parser = Parser::new();
-    parser
-        .set_language(&tree_sitter_motoko::LANGUAGE.into())
-        .expect("Error loading Motoko grammar");
-    let tree = parser.parse(input.as_bytes(), None).unwrap();
-    let mut diagnostics = Vec::new();
-    for rule in rules {
-        if !rule.applies_to(path) {
-            continue;
-        }
-        diagnostics.extend(apply_rule(rule, tree.root_node(), input)?);
+    let result = lint(config, path, input, rules)?;
+    if !result.tool_diagnostics.is_empty() {
+        let failures: Vec<String> = result
+            .tool_diagnostics
+            .iter()
+            // Rules that failed to compile already name themselves
+            .map(|d| match (&d.path, &d.rule) {
+                (Some(_), Some(rule)) => format!("Rule '{rule}' failed: {}", d.message),
+                _ => d.message.clone(),
+            })
+            .collect();
+        bail!("{}", failures.join("\n"))
     }
-    if let Some(severity) = config.severity_override {
-        for d in &mut diagnostics {
-            d.severity = severity;
-        }
+    // Aggregated formats are only written once all files are linted, see `Reporter`
+    if !config.format.is_aggregated() {
+        renderer(config).file(path, input, &result.diagnostics, &mut out)?;
     }
-    diagnostics.sort_by_key(|d| d.range.start_byte);
-    for diagnostic in &diagnostics {
-        let output = match config.format {
-            OutputFormat::Pretty => print_pretty_diagnostic(path, input, diagnostic),
-            OutputFormat::Text => print_text_diagnostic(path, input, diagnostic),
-        };
-        writeln!(&mut out, "{output}")?
-    }
-    let mut fixed_file = None;
-    let mut overlaps = false;
-    if config.fix {
-        diagnostics.reverse();
-        let mut output = input.to_string();
-        let mut last_range: Option<Range> = None;
-        for diagnostic in &diagnostics {
-            if let Some(fixed) = &diagnostic.fix {
-                // NOTE: Don't try to fix overlapping ranges. Instead requires running the tool to a fixpoint
-                // Would be nice to automate in the future
-                if let Some(last_range) = last_range
-                    && diagnostic.range.end_byte >= last_range.start_byte
-                {
-                    overlaps = true;
-                    continue;
-                }
-                output.replace_range(
-                    diagnostic.range.start_byte..diagnostic.range.end_byte,
-                    fixed,
-                );
-                last_range = Some(diagnostic.range)
-            }
-        }
-        if output != input {
-            fixed_file = Some(output)
-        }
+    if result.fix_overlaps {
+        writeln!(&mut out, "{FIX_OVERLAPS_MESSAGE}")?
     }
-    if overlaps {
-        writeln!(
-            &mut out,
-            "Spotted overlaps when applying fixes. Re-run the command to make progress"
-        )?
-    }
-
-    let (error_count, warning_count) =
-        diagnostics
-            .iter()
-            .fold((0, 0), |(e, w), d| match d.severity {
-                RuleSeverity::Error => (e + 1, w),
-                RuleSeverity::Warning => (e, w + 1),
-            });
-
-    Ok(LintResult {
-        error_count,
-        warning_count,
-        fixed_file,
-    })
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -395,6 +587,96 @@ mod test {
         insta::assert_snapshot!(lint_output);
     }
 
+    fn escalating_rule() -> Rule {
+        toml::from_str(
+            r#"
+            name = "pun-fields"
+            description = "Use field punning"
+            query = "(exp_field (var_field) @error)"
+            severity = "warning"
+            escalate = { to = "error", after = "2027-03-01" }
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn config_on(today: jiff::civil::Date) -> Config {
+        Config {
+            today: Some(today),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn rules_escalate_once_the_date_has_passed() {
+        let rule = escalating_rule();
+        assert_eq!(
+            rule.severity_on(jiff::civil::date(2027, 3, 1)),
+            RuleSeverity::Warning
+        );
+        assert_eq!(
+            rule.severity_on(jiff::civil::date(2027, 3, 2)),
+            RuleSeverity::Error
+        );
+
+        let res = lint(
+            &config_on(jiff::civil::date(2027, 3, 2)),
+            "<input_path>",
+            "{ x = x }",
+            &[rule],
+        )
+        .unwrap();
+        assert_eq!(res.error_count, 1);
+        assert_eq!(res.diagnostics[0].escalation, None);
+    }
+
+    #[test]
+    fn pretty_output_mentions_upcoming_escalations() {
+        let mut out: Vec<u8> = vec![];
+        lint_file(
+            &config_on(jiff::civil::date(2026, 1, 1)),
+            "<input_path>",
+            "{ x = x }",
+            &[escalating_rule()],
+            &mut out,
+        )
+        .unwrap();
+        let output = str::from_utf8(&out).unwrap();
+        assert!(
+            output.contains("This rule escalates to `error` after 2027-03-01"),
+            "{output}"
+        );
+
+        // A pinned severity doesn't escalate
+        let mut rule = escalating_rule();
+        rule.pin_severity(RuleSeverity::Warning);
+        let mut out: Vec<u8> = vec![];
+        lint_file(
+            &config_on(jiff::civil::date(2026, 1, 1)),
+            "<input_path>",
+            "{ x = x }",
+            &[rule],
+            &mut out,
+        )
+        .unwrap();
+        let output = str::from_utf8(&out).unwrap();
+        assert!(!output.contains("escalates"), "{output}");
+    }
+
+    #[test]
+    fn invalid_escalation_dates_are_rejected() {
+        let err = toml::from_str::<Rule>(
+            r#"
+            name = "test"
+            description = "test"
+            query = "(source_file) @error"
+            escalate = { to = "error", after = "next year" }
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("invalid date"), "{err}");
+    }
+
     fn rule_with_filters(includes: &[&str], excludes: &[&str]) -> Rule {
         let compile = |pats: &[&str]| pats.iter().map(|p| Pattern::new(p).unwrap()).collect();
         Rule {
@@ -488,6 +770,41 @@ includes = ["[unterminated"]
         assert_errors(&rule, only_types, "backend/types/foo.mo", 0);
     }
 
+    #[test]
+    fn it_lints_with_compact_output() {
+        let rule = Rule {
+            name: "no-let-else".into(),
+            description: "Do not use\nlet-else".into(),
+            ..test_rule("(let_else_dec) @error")
+        };
+        let src = "let a = 1;\n  let ?x = y else { return };";
+        for (end_positions, expected) in [
+            (
+                false,
+                "main.mo:2:3: error: Do not use let-else [no-let-else]\n",
+            ),
+            (
+                true,
+                "main.mo:2:3:2:29: error: Do not use let-else [no-let-else]\n",
+            ),
+        ] {
+            let mut out: Vec<u8> = vec![];
+            lint_file(
+                &Config {
+                    format: OutputFormat::Compact,
+                    end_positions,
+                    ..Config::default()
+                },
+                "main.mo",
+                src,
+                std::slice::from_ref(&rule),
+                &mut out,
+            )
+            .unwrap();
+            assert_eq!(str::from_utf8(&out).unwrap(), expected);
+        }
+    }
+
     #[test]
     fn it_applies_fixes() {
         let mut out: Vec<u8> = vec![];
@@ -527,6 +844,61 @@ includes = ["[unterminated"]
         assert_eq!(res.warning_count, 1);
     }
 
+    #[test]
+    fn advisory_severities_are_counted_separately() {
+        let mut rule = load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap();
+        rule.severity = RuleSeverity::Info;
+        let res = lint(
+            &Config::default(),
+            "<input_path>",
+            "{ x = x }",
+            &[rule.clone()],
+        )
+        .unwrap();
+        assert_eq!((res.error_count, res.warning_count), (0, 0));
+        assert_eq!(res.info_count, 1);
+        rule.severity = RuleSeverity::Hint;
+        let res = lint(&Config::default(), "<input_path>", "{ x = x }", &[rule]).unwrap();
+        assert_eq!(res.hint_count, 1);
+        assert!(res.diagnostics[0].severity.is_advisory());
+    }
+
+    #[test]
+    fn broken_rules_do_not_stop_other_rules() {
+        let broken = Rule {
+            name: "broken".into(),
+            ..test_rule("(not_a_node) @error")
+        };
+        let rules = [
+            broken,
+            load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap(),
+        ];
+        let res = lint(&Config::default(), "<input_path>", "{ x = x }", &rules).unwrap();
+        assert_eq!(res.warning_count, 1);
+        assert_eq!(res.tool_diagnostics.len(), 1);
+        assert_eq!(res.tool_diagnostics[0].rule.as_deref(), Some("broken"));
+        assert_eq!(res.tool_diagnostics[0].path, None);
+    }
+
+    #[test]
+    fn off_rules_are_not_run() {
+        let mut rule = load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap();
+        rule.severity = RuleSeverity::Off;
+        let res = lint(&Config::default(), "<input_path>", "{ x = x }", &[rule]).unwrap();
+        assert!(res.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn severity_override_off_runs_no_rules() {
+        let rule = load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap();
+        let config = Config {
+            severity_override: Some(RuleSeverity::Off),
+            ..Config::default()
+        };
+        let res = lint(&config, "<input_path>", "{ x = x }", &[rule]).unwrap();
+        assert!(res.diagnostics.is_empty());
+    }
+
     #[test]
     fn severity_override_promotes_warnings_to_errors() {
         let mut out: Vec<u8> = vec![];
@@ -0,0 +1,764 @@
+use crate::custom_predicates::{self, MatchEvaluator, SpecialCaptures};
+use crate::{
+    Config, Diagnostic, LintResult, Range, Rule, RuleSeverity, ToolDiagnostic, structural_context,
+    suppression, template,
+};
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::debug;
+use tree_sitter::{
+    InputEdit, Node, Parser, Point, Query, QueryCapture, QueryCursor, QueryMatch,
+    StreamingIterator, Tree,
+};
+
+/// A rule with its query compiled, ready to run on any number of files
+struct CompiledRule {
+    /// Index into `Linter::rules`
+    rule: usize,
+    query: Query,
+    captures: SpecialCaptures,
+}
+
+/// Compiles the query of `rule`, and checks everything that doesn't depend on the linted file
+fn compile(rule: &Rule) -> Result<(Query, SpecialCaptures)> {
+    let query = Query::new(&tree_sitter_motoko::LANGUAGE.into(), &rule.query)
+        .with_context(|| format!("Failed to create query for rule '{}'", rule.name))?;
+    let captures = SpecialCaptures::new(&query)
+        .with_context(|| format!("Invalid query for rule '{}':\n{}", rule.name, rule.query))?;
+    custom_predicates::validate(&query)
+        .with_context(|| format!("Invalid predicate in rule '{}'", rule.name))?;
+    let re = Regex::new(r"@([a-z-]+)").unwrap();
+    for template in [Some(&rule.description), rule.fix.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        for caps in re.captures_iter(template) {
+            let name = &caps[1];
+            if query.capture_index_for_name(name).is_none() {
+                bail!(
+                    "Rule '{}' mentions '@{name}', which isn't captured by its query:\n\n'{template}'",
+                    rule.name
+                )
+            }
+        }
+    }
+    Ok((query, captures))
+}
+
+/// The `@error` and `@filter` captures of one rule's matches in a file
+struct RuleMatches<'tree> {
+    errors: Vec<(Range, String, Vec<QueryCapture<'tree>>)>,
+    filtered: HashSet<Range>,
+}
+
+impl<'tree> RuleMatches<'tree> {
+    fn new() -> Self {
+        Self {
+            errors: vec![],
+            filtered: HashSet::new(),
+        }
+    }
+
+    fn add(&mut self, m: &QueryMatch<'_, 'tree>, error_capture: u32, evaluator: &MatchEvaluator) {
+        for error_node in m.nodes_for_capture_index(error_capture) {
+            // NOTE: We have to use `to_vec` here, or tree-sitter will silently swap the captures under our feet.
+            self.errors.push((
+                error_node.range(),
+                structural_context(error_node),
+                m.captures.to_vec(),
+            ));
+        }
+        evaluator.collect_filter_ranges(m, &mut self.filtered);
+    }
+
+    /// Templates descriptions and fixes with the captures of the matches. `query` is the query that matched
+    fn into_diagnostics(self, rule: &Rule, query: &Query, input: &str) -> Result<Vec<Diagnostic>> {
+        let mut seen = HashSet::new();
+        let mut diagnostics = vec![];
+        for (range, context, captures) in self.errors {
+            if self.filtered.contains(&range) {
+                continue;
+            }
+            // Avoid reporting the same diagnostic twice on the same range
+            if !seen.insert(range) {
+                continue;
+            }
+            let description = template(&rule.description, query, &captures, input)?;
+            let fix = if let Some(ref fix_template) = rule.fix {
+                Some(template(fix_template, query, &captures, input)?)
+            } else {
+                None
+            };
+
+            let diagnostic = Diagnostic {
+                rule: rule.name.to_string(),
+                description,
+                range,
+                fix,
+                severity: rule.severity,
+                context,
+                escalation: rule.escalate,
+            };
+            diagnostics.push(diagnostic);
+        }
+        Ok(diagnostics)
+    }
+}
+
+impl CompiledRule {
+    fn apply(&self, rule: &Rule, tree: Node, input: &str) -> Result<Vec<Diagnostic>> {
+        let mut evaluator = MatchEvaluator::new(&self.query, self.captures);
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&self.query, tree, input.as_bytes());
+        let mut found = RuleMatches::new();
+        while let Some(m) = matches.next() {
+            if evaluator.should_skip(m)? {
+                continue;
+            }
+            found.add(m, self.captures.error, &evaluator);
+        }
+        found.into_diagnostics(rule, &self.query, input)
+    }
+}
+
+/// The queries of all compiled rules merged into one, so the syntax tree is only walked once per file
+struct CombinedQuery {
+    query: Query,
+    captures: SpecialCaptures,
+    /// Where the patterns of each compiled rule start in the combined source, in the order of `compiled`
+    offsets: Vec<usize>,
+}
+
+impl CombinedQuery {
+    fn new(rules: &[Rule], compiled: &[CompiledRule]) -> Result<Self> {
+        let mut source = String::new();
+        let mut offsets = vec![];
+        for c in compiled {
+            offsets.push(source.len());
+            source.push_str(&rules[c.rule].query);
+            // Terminates trailing comments
+            source.push('\n');
+        }
+        let query = Query::new(&tree_sitter_motoko::LANGUAGE.into(), &source)?;
+        let captures = SpecialCaptures::new(&query)?;
+        Ok(Self {
+            query,
+            captures,
+            offsets,
+        })
+    }
+
+    /// The index into `compiled` of the rule that `pattern` comes from
+    fn rule_of(&self, pattern: usize) -> usize {
+        let start = self.query.start_byte_for_pattern(pattern);
+        self.offsets.partition_point(|offset| *offset <= start) - 1
+    }
+
+    /// Runs all `active` rules, returning their diagnostics in the order of `compiled`
+    fn apply(
+        &self,
+        shared: &CompiledRules,
+        active: &[bool],
+        tree: Node,
+        input: &str,
+    ) -> Vec<(usize, Result<Vec<Diagnostic>>)> {
+        let mut evaluator = MatchEvaluator::new(&self.query, self.captures);
+        let mut found: Vec<_> = active.iter().map(|_| RuleMatches::new()).collect();
+        let mut failed: Vec<Option<anyhow::Error>> = active.iter().map(|_| None).collect();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&self.query, tree, input.as_bytes());
+        while let Some(m) = matches.next() {
+            let i = self.rule_of(m.pattern_index);
+            if !active[i] || failed[i].is_some() {
+                continue;
+            }
+            match evaluator.should_skip(m) {
+                Ok(true) => {}
+                Ok(false) => found[i].add(m, self.captures.error, &evaluator),
+                Err(err) => failed[i] = Some(err),
+            }
+        }
+        found
+            .into_iter()
+            .zip(failed)
+            .enumerate()
+            .filter(|(i, _)| active[*i])
+            .map(|(i, (found, failed))| {
+                let rule = &shared.rules[shared.compiled[i].rule];
+                let result = match failed {
+                    Some(err) => Err(err),
+                    None => found.into_diagnostics(rule, &self.query, input),
+                };
+                (i, result)
+            })
+            .collect()
+    }
+}
+
+/// How a `Linter` runs its rules
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Merges all queries into one, and walks the syntax tree once per file
+    #[default]
+    Combined,
+    /// Walks the syntax tree once per rule
+    PerRule,
+}
+
+/// The part of a `Linter` that doesn't change between files
+struct CompiledRules {
+    rules: Vec<Rule>,
+    compiled: Vec<CompiledRule>,
+    rule_errors: Vec<ToolDiagnostic>,
+    /// `None` if there are no rules to combine
+    combined: Option<CombinedQuery>,
+}
+
+fn motoko_parser() -> Parser {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_motoko::LANGUAGE.into())
+        .expect("Error loading Motoko grammar");
+    parser
+}
+
+/// Lints any number of files with the same rules. All queries are compiled once up front, and the parser is
+/// reused between files.
+///
+/// Clones share the compiled queries, but get their own parser. Use one clone per thread to lint in parallel.
+pub struct Linter {
+    parser: Parser,
+    shared: Arc<CompiledRules>,
+    strategy: Strategy,
+    known_rules: Arc<[String]>,
+}
+
+impl Clone for Linter {
+    fn clone(&self) -> Self {
+        Self {
+            parser: motoko_parser(),
+            shared: self.shared.clone(),
+            strategy: self.strategy,
+            known_rules: self.known_rules.clone(),
+        }
+    }
+}
+
+impl Linter {
+    /// Compiles the queries of all rules. Rules that fail to compile are listed in `rule_errors`, and skipped
+    /// when linting
+    pub fn new(rules: &[Rule]) -> Self {
+        let mut compiled = vec![];
+        let mut rule_errors = vec![];
+        for (i, rule) in rules.iter().enumerate() {
+            // Rules that are off never run, so they can't fail either. Unless they escalate later
+            if rule.severity == RuleSeverity::Off && rule.escalate.is_none() {
+                continue;
+            }
+            match compile(rule) {
+                Ok((query, captures)) => compiled.push(CompiledRule {
+                    rule: i,
+                    query,
+                    captures,
+                }),
+                Err(err) => rule_errors.push(ToolDiagnostic {
+                    path: None,
+                    rule: Some(rule.name.clone()),
+                    message: format!("{err:#}"),
+                }),
+            }
+        }
+        let combined = if compiled.is_empty() {
+            None
+        } else {
+            // Every query compiled on its own, so this is not expected to fail
+            CombinedQuery::new(rules, &compiled)
+                .inspect_err(|err| {
+                    debug!("Running rules one by one, failed to combine them: {err:#}")
+                })
+                .ok()
+        };
+        Self {
+            parser: motoko_parser(),
+            shared: Arc::new(CompiledRules {
+                rules: rules.to_vec(),
+                compiled,
+                rule_errors,
+                combined,
+            }),
+            strategy: Strategy::default(),
+            known_rules: rules.iter().map(|r| r.name.clone()).collect(),
+        }
+    }
+
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets the names of all rules that exist, including the ones this linter doesn't run, e.g. because
+    /// they are disabled. The suppression audit reports suppressions for any other rule as unknown
+    pub fn with_known_rules<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.known_rules = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The rules suppressions can name, by default the ones passed to `new`
+    pub fn known_rules(&self) -> &[String] {
+        &self.known_rules
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.shared.rules
+    }
+
+    /// The rules that failed to compile
+    pub fn rule_errors(&self) -> &[ToolDiagnostic] {
+        &self.shared.rule_errors
+    }
+
+    /// Lints a single file without printing anything. Use a `Reporter` to render the result
+    pub fn lint(&mut self, config: &Config, path: &str, input: &str) -> Result<LintResult> {
+        self.lint_filtered(config, path, input, |_| true)
+    }
+
+    /// Like `lint`, but only runs the rules `include` returns true for, e.g. to apply path-scoped overrides
+    pub fn lint_filtered(
+        &mut self,
+        config: &Config,
+        path: &str,
+        input: &str,
+        include: impl Fn(&Rule) -> bool,
+    ) -> Result<LintResult> {
+        let tree = self.parser.parse(input.as_bytes(), None).unwrap();
+        self.lint_tree(config, path, input, &tree, include)
+    }
+
+    /// Like `lint_filtered`, but parses `input` as an edit of `previous`, reusing the unchanged parts of its
+    /// syntax tree. Returns the parsed file to pass in after the next edit
+    pub fn lint_incremental(
+        &mut self,
+        config: &Config,
+        path: &str,
+        input: &str,
+        previous: Option<ParsedFile>,
+        include: impl Fn(&Rule) -> bool,
+    ) -> Result<(LintResult, ParsedFile)> {
+        let tree = match previous {
+            Some(previous) if previous.source == input => previous.tree,
+            Some(mut previous) => {
+                previous.tree.edit(&input_edit(&previous.source, input));
+                self.parser
+                    .parse(input.as_bytes(), Some(&previous.tree))
+                    .unwrap()
+            }
+            None => self.parser.parse(input.as_bytes(), None).unwrap(),
+        };
+        let result = self.lint_tree(config, path, input, &tree, include)?;
+        let parsed = ParsedFile {
+            source: input.to_string(),
+            tree,
+        };
+        Ok((result, parsed))
+    }
+
+    /// Lints a file that was linted before with different rules, without parsing it again. Only the rules
+    /// named in `changed` run. The findings of all other rules are taken from `previous`
+    pub fn relint_rules(
+        &mut self,
+        config: &Config,
+        path: &str,
+        parsed: &ParsedFile,
+        previous: &LintResult,
+        changed: &[String],
+        include: impl Fn(&Rule) -> bool,
+    ) -> Result<LintResult> {
+        let (input, tree) = (&parsed.source, &parsed.tree);
+        if config.audit_suppressions {
+            // Whether a suppression is used depends on the findings of all rules
+            return self.lint_tree(config, path, input, tree, include);
+        }
+        let is_changed = |name: &str| changed.iter().any(|c| c == name);
+        let fresh = self.lint_tree(config, path, input, tree, |rule| {
+            is_changed(&rule.name) && include(rule)
+        })?;
+        let mut diagnostics: Vec<Diagnostic> = previous
+            .diagnostics
+            .iter()
+            // `fresh` reports broken suppressions again
+            .filter(|d| {
+                !is_changed(&d.rule) && !suppression::CHECK_RULES.contains(&d.rule.as_str())
+            })
+            .cloned()
+            .chain(fresh.diagnostics)
+            .collect();
+        diagnostics.sort_by_key(|d| d.range.start_byte);
+        let tool_diagnostics = previous
+            .tool_diagnostics
+            .iter()
+            .filter(|t| !t.rule.as_deref().is_some_and(is_changed))
+            .cloned()
+            .chain(fresh.tool_diagnostics)
+            .collect();
+        Ok(finish(config, input, diagnostics, tool_diagnostics))
+    }
+
+    fn lint_tree(
+        &self,
+        config: &Config,
+        path: &str,
+        input: &str,
+        tree: &Tree,
+        include: impl Fn(&Rule) -> bool,
+    ) -> Result<LintResult> {
+        let mut diagnostics = Vec::new();
+        let mut tool_diagnostics = Vec::new();
+        let shared = &self.shared;
+        let today = config.today();
+        let all_off = config.severity_override == Some(RuleSeverity::Off);
+        let active: Vec<bool> = shared
+            .compiled
+            .iter()
+            .map(|c| {
+                let rule = &shared.rules[c.rule];
+                !all_off
+                    && rule.severity_on(today) != RuleSeverity::Off
+                    && rule.applies_to(path)
+                    && include(rule)
+            })
+            .collect();
+        let results = match (&shared.combined, self.strategy) {
+            (Some(combined), Strategy::Combined) => {
+                combined.apply(shared, &active, tree.root_node(), input)
+            }
+            _ => shared
+                .compiled
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| active[*i])
+                .map(|(i, c)| (i, c.apply(&shared.rules[c.rule], tree.root_node(), input)))
+                .collect(),
+        };
+        // Suppressions for rules that didn't run on the file can't be told to be unused
+        let mut ran: Vec<&str> = vec![];
+        for (i, result) in results {
+            match result {
+                Ok(found) => {
+                    ran.push(&shared.rules[shared.compiled[i].rule].name);
+                    diagnostics.extend(found);
+                }
+                Err(err) => tool_diagnostics.push(ToolDiagnostic {
+                    path: Some(path.to_string()),
+                    rule: Some(shared.rules[shared.compiled[i].rule].name.clone()),
+                    message: format!("{err:#}"),
+                }),
+            }
+        }
+        for d in &mut diagnostics {
+            if let Some(escalation) = d.escalation
+                && escalation.has_passed(today)
+            {
+                d.severity = escalation.to;
+                d.escalation = None;
+            }
+        }
+        let suppressions = suppression::collect(tree.root_node(), input);
+        let used = suppression::apply(&suppressions, today, &mut diagnostics);
+        if let Some(severity) = config.severity_override {
+            for d in &mut diagnostics {
+                // `--severity` pins the severity, so there's nothing left to escalate
+                d.severity = severity;
+                d.escalation = None;
+            }
+        }
+        diagnostics.extend(suppression::check(&suppressions, today));
+        if config.audit_suppressions {
+            diagnostics.extend(suppression::audit(
+                &suppressions,
+                &used,
+                &self.known_rules,
+                &ran,
+                today,
+            ));
+        }
+        diagnostics.sort_by_key(|d| d.range.start_byte);
+        Ok(finish(config, input, diagnostics, tool_diagnostics))
+    }
+}
+
+/// The source and syntax tree of a linted file, to parse the next version of the file incrementally
+#[derive(Debug, Clone)]
+pub struct ParsedFile {
+    source: String,
+    tree: Tree,
+}
+
+impl ParsedFile {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+/// The position of `byte` in `text`
+fn point_at(text: &str, byte: usize) -> Point {
+    let before = &text.as_bytes()[..byte];
+    let row = before.iter().filter(|b| **b == b'\n').count();
+    let line_start = before
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
+    Point::new(row, byte - line_start)
+}
+
+/// A single edit that turns `old` into `new`, replacing everything between their common prefix and suffix
+fn input_edit(old: &str, new: &str) -> InputEdit {
+    let (old_bytes, new_bytes) = (old.as_bytes(), new.as_bytes());
+    let prefix = old_bytes
+        .iter()
+        .zip(new_bytes)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_bytes[prefix..]
+        .iter()
+        .rev()
+        .zip(new_bytes[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_end = old.len() - suffix;
+    let new_end = new.len() - suffix;
+    InputEdit {
+        start_byte: prefix,
+        old_end_byte: old_end,
+        new_end_byte: new_end,
+        start_position: point_at(old, prefix),
+        old_end_position: point_at(old, old_end),
+        new_end_position: point_at(new, new_end),
+    }
+}
+
+/// Applies the fixes of the sorted `diagnostics` if `config.fix` is set, and counts them
+/// Applies the fixes of `diagnostics` to `input`. Returns the fixed file, if anything changed, and
+/// whether some fixes were skipped because they overlapped
+pub(crate) fn apply_fixes(input: &str, diagnostics: &[Diagnostic]) -> (Option<String>, bool) {
+    let mut output = input.to_string();
+    let mut overlaps = false;
+    let mut last_range: Option<Range> = None;
+    for diagnostic in diagnostics.iter().rev() {
+        if let Some(fixed) = &diagnostic.fix {
+            // NOTE: Don't try to fix overlapping ranges. Instead requires running the tool to a fixpoint
+            // Would be nice to automate in the future
+            if let Some(last_range) = last_range
+                && diagnostic.range.end_byte >= last_range.start_byte
+            {
+                overlaps = true;
+                continue;
+            }
+            output.replace_range(
+                diagnostic.range.start_byte..diagnostic.range.end_byte,
+                fixed,
+            );
+            last_range = Some(diagnostic.range)
+        }
+    }
+    ((output != input).then_some(output), overlaps)
+}
+
+pub(crate) fn finish(
+    config: &Config,
+    input: &str,
+    diagnostics: Vec<Diagnostic>,
+    tool_diagnostics: Vec<ToolDiagnostic>,
+) -> LintResult {
+    let (fixed_file, overlaps) = if config.fix {
+        apply_fixes(input, &diagnostics)
+    } else {
+        (None, false)
+    };
+    let mut result = LintResult {
+        fixed_file,
+        fix_overlaps: overlaps,
+        diagnostics,
+        tool_diagnostics,
+        ..LintResult::default()
+    };
+    result.count_severities();
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{load_rule_from_file, test_rule};
+    use std::path::Path;
+
+    #[test]
+    fn broken_rules_fail_when_compiled() {
+        let rules = [
+            Rule {
+                name: "bad-query".into(),
+                ..test_rule("(not_a_node) @error")
+            },
+            Rule {
+                name: "no-error-capture".into(),
+                ..test_rule("(let_dec) @dec")
+            },
+            Rule {
+                name: "bad-template".into(),
+                description: "Found @missing".into(),
+                ..test_rule("(let_dec) @error")
+            },
+            Rule {
+                name: "bad-predicate".into(),
+                ..test_rule(r#"((let_dec) @error (#bogus-pred? @error "x"))"#)
+            },
+            Rule {
+                name: "off".into(),
+                severity: RuleSeverity::Off,
+                ..test_rule("(not_a_node) @error")
+            },
+        ];
+        let linter = Linter::new(&rules);
+        let failed: Vec<_> = linter
+            .rule_errors()
+            .iter()
+            .map(|e| e.rule.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                "bad-query",
+                "no-error-capture",
+                "bad-template",
+                "bad-predicate"
+            ]
+        );
+        assert!(linter.rule_errors().iter().all(|e| e.path.is_none()));
+    }
+
+    #[test]
+    fn strategies_find_the_same_diagnostics() {
+        let rules = crate::load_rules_from_directory(Path::new("example-rules")).unwrap();
+        let input = include_str!("../test-data.mo");
+        let config = Config {
+            fix: true,
+            ..Config::default()
+        };
+        let combined = Linter::new(&rules)
+            .lint(&config, "test-data.mo", input)
+            .unwrap();
+        let per_rule = Linter::new(&rules)
+            .with_strategy(Strategy::PerRule)
+            .lint(&config, "test-data.mo", input)
+            .unwrap();
+        let describe = |result: &LintResult| -> Vec<_> {
+            result
+                .diagnostics
+                .iter()
+                .map(|d| (d.rule.clone(), d.range, d.description.clone()))
+                .collect()
+        };
+        assert!(!combined.diagnostics.is_empty());
+        assert_eq!(describe(&combined), describe(&per_rule));
+        assert_eq!(combined.fixed_file, per_rule.fixed_file);
+    }
+
+    #[test]
+    fn linters_are_reused_across_files() {
+        let rule = load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap();
+        let mut linter = Linter::new(&[rule]);
+        let config = Config::default();
+        let first = linter.lint(&config, "a.mo", "{ x = x }").unwrap();
+        let second = linter.lint(&config, "b.mo", "{ x; y = y; z = z }").unwrap();
+        assert_eq!(first.warning_count, 1);
+        assert_eq!(second.warning_count, 2);
+    }
+
+    #[test]
+    fn edits_span_the_changed_bytes() {
+        let edit = input_edit("let a = 1;\nlet b = 2;", "let a = 1;\nlet bc = 23;");
+        assert_eq!(edit.start_byte, 16);
+        assert_eq!(edit.old_end_byte, 20);
+        assert_eq!(edit.new_end_byte, 22);
+        assert_eq!(edit.start_position, Point::new(1, 5));
+        assert_eq!(edit.old_end_position, Point::new(1, 9));
+        assert_eq!(edit.new_end_position, Point::new(1, 11));
+
+        let edit = input_edit("aaa", "aa");
+        assert_eq!(
+            (edit.start_byte, edit.old_end_byte, edit.new_end_byte),
+            (2, 3, 2)
+        );
+    }
+
+    #[test]
+    fn incremental_parses_find_the_same_diagnostics() {
+        let rule = load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap();
+        let mut linter = Linter::new(&[rule]);
+        let config = Config::default();
+        let versions = [
+            "let a = { x = x };\nlet b = { y };",
+            "let a = { x = x };\nlet c = 1;\nlet b = { y = y };",
+            "let b = { y = y };",
+            "",
+            "let a = { x = x; z = z };",
+        ];
+        let mut previous = None;
+        for version in versions {
+            let (incremental, parsed) = linter
+                .lint_incremental(&config, "main.mo", version, previous, |_| true)
+                .unwrap();
+            let fresh = linter.lint(&config, "main.mo", version).unwrap();
+            let ranges = |result: &LintResult| -> Vec<Range> {
+                result.diagnostics.iter().map(|d| d.range).collect()
+            };
+            assert_eq!(ranges(&incremental), ranges(&fresh), "{version}");
+            assert_eq!(parsed.source(), version);
+            previous = Some(parsed);
+        }
+    }
+
+    #[test]
+    fn only_changed_rules_are_relinted() {
+        let rules = [
+            load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap(),
+            load_rule_from_file(Path::new("example-rules/no-let-else.toml")).unwrap(),
+        ];
+        let source = "let a = { x = x };\nlet ?x = y else { return };";
+        let config = Config::default();
+        let (previous, parsed) = Linter::new(&rules)
+            .lint_incremental(&config, "main.mo", source, None, |_| true)
+            .unwrap();
+        assert_eq!(previous.diagnostics.len(), 2);
+
+        let changed = [
+            Rule {
+                severity: RuleSeverity::Error,
+                ..rules[0].clone()
+            },
+            rules[1].clone(),
+        ];
+        let mut linter = Linter::new(&changed);
+        let relinted = linter
+            .relint_rules(
+                &config,
+                "main.mo",
+                &parsed,
+                &previous,
+                &["pun-fields".to_string()],
+                |_| true,
+            )
+            .unwrap();
+        let fresh = linter.lint(&config, "main.mo", source).unwrap();
+        let summary = |result: &LintResult| -> Vec<String> {
+            result
+                .diagnostics
+                .iter()
+                .map(|d| format!("{d:?}"))
+                .collect()
+        };
+        assert_eq!(summary(&relinted), summary(&fresh));
+        assert_eq!(relinted.error_count, 2);
+    }
+}
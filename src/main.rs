@@ -1,15 +1,28 @@
 use anyhow::{Context, Result, anyhow, bail};
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use glob::glob_with;
 use std::fs;
+use std::io::{BufWriter, Write};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
-use std::{collections::BTreeSet, path::Path};
-use tracing::{debug, level_filters::LevelFilter};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+use tracing::{debug, level_filters::LevelFilter, warn};
+
+mod watch;
 
 /// An extensible linter for Motoko
 #[derive(Parser, Debug)]
 #[command(about, version)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Files, directories, or globs of Motoko files to lint
     #[arg(value_name = "INPUTS")]
     inputs: Vec<String>,
@@ -22,23 +35,131 @@ struct Args {
     #[arg(long)]
     fix: bool,
 
-    /// Output format
-    #[arg(short, long, value_enum, default_value_t=OutputFormat::Pretty)]
-    format: OutputFormat,
+    /// Output format, optionally followed by `=PATH` to write it to a file. Can be passed multiple times
+    ///
+    /// All formats are rendered from the same lint run, e.g. `-f pretty -f sarif=out.sarif`.
+    /// Possible values: pretty, text, compact, github, json, sarif, checkstyle, junit, gitlab, html, markdown.
+    /// Defaults to the `format` in `lintoko.toml`, or pretty
+    #[arg(short, long = "format", value_name = "FORMAT[=PATH]", value_parser = parse_format_arg)]
+    formats: Vec<FormatArg>,
+
+    /// Write the output to this file instead of the terminal, for a format passed without `=PATH`
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
 
-    /// Directories containing rules. Can be passed multiple times
+    /// Directories containing rules. Can be passed multiple times. Replaces the `rules` in `lintoko.toml`
     ///
     /// When passing a file path, will _only_ use the rule in that file
     #[arg(short, long, value_name = "DIRECTORY")]
     rules: Vec<PathBuf>,
 
-    /// Override severity for all rules
+    /// Also print end positions (`path:line:col:end_line:end_col: ...`) in the compact format
+    #[arg(long)]
+    end_positions: bool,
+
+    /// Maximum number of findings listed in the markdown format. The summary table still counts all of them
+    #[arg(long, value_name = "N")]
+    max_findings: Option<usize>,
+
+    /// Override severity for all rules. Use `--disable` or `--only` to turn off rules
     #[arg(short, long, value_enum)]
-    severity: Option<CliSeverity>,
+    severity: Option<OverrideSeverity>,
+
+    /// Only report findings that aren't recorded in this baseline file
+    #[arg(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+
+    /// Record all current findings in this baseline file, and exit successfully
+    #[arg(long, value_name = "FILE")]
+    write_baseline: Option<PathBuf>,
+
+    /// Override the severity of a single rule, e.g. `--rule-severity pun-fields=error`. Can be passed multiple times
+    #[arg(long, value_name = "RULE=SEVERITY", value_parser = parse_rule_severity)]
+    rule_severity: Vec<(String, lintoko::RuleSeverity)>,
+
+    /// Don't run these rules. Can be passed multiple times, or as a comma-separated list
+    #[arg(long, value_name = "RULE", value_delimiter = ',')]
+    disable: Vec<String>,
+
+    /// Only run these rules. Can be passed multiple times, or as a comma-separated list
+    #[arg(long, value_name = "RULE", value_delimiter = ',')]
+    only: Vec<String>,
+
+    /// Also report suppression comments that are unused, name unknown rules, have no `-- reason`, or have expired
+    #[arg(long)]
+    audit_suppressions: bool,
+
+    /// Fail when there are more than N warnings
+    #[arg(long, value_name = "N")]
+    max_warnings: Option<usize>,
+
+    /// Fail on any warning. Same as `--max-warnings 0`
+    #[arg(long, conflicts_with = "max_warnings")]
+    deny_warnings: bool,
+
+    /// Number of files to lint in parallel. Defaults to the number of available CPUs
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+
+    /// Replay the results of unchanged files from `.lintoko-cache`. Can be enabled with `cache = true` in `lintoko.toml`
+    #[arg(long)]
+    cache: bool,
+
+    /// Don't use the cache, even if `lintoko.toml` enables it
+    #[arg(long, conflicts_with = "cache")]
+    no_cache: bool,
+
+    /// Remove all cached results before linting
+    #[arg(long)]
+    clear_cache: bool,
+
+    /// Keep running, and lint files again whenever they or the rules change
+    #[arg(
+        long,
+        conflicts_with_all = ["fix", "output", "baseline", "write_baseline", "cache", "clear_cache", "jobs"]
+    )]
+    watch: bool,
+}
+
+// Exit codes, documented in the README. Wrapper scripts rely on them to tell broken setups apart from findings
+const EXIT_LINT_FAILURES: u8 = 1;
+const EXIT_CONFIG_ERROR: u8 = 2;
+const EXIT_INTERNAL_ERROR: u8 = 3;
+
+/// Marks errors in the configuration, the rules or the arguments, as opposed to I/O or internal errors
+#[derive(Debug)]
+struct ConfigError;
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Invalid configuration")
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List all suppression comments as a JSON document
+    Suppressions {
+        /// Files, directories, or globs of Motoko files to search
+        #[arg(value_name = "INPUTS")]
+        inputs: Vec<String>,
+    },
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum CliSeverity {
+    Off,
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+/// The severities `--severity` accepts. Unlike a single rule, all rules can't be turned off at once
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum OverrideSeverity {
+    Hint,
+    Info,
     Warning,
     Error,
 }
@@ -49,6 +170,103 @@ enum OutputFormat {
     Pretty,
     /// Text output
     Text,
+    /// One line per diagnostic: `path:line:col: severity: message [rule]`
+    Compact,
+    /// GitHub Actions workflow commands, to annotate pull requests inline
+    Github,
+    /// A single JSON document for the whole run, written to stdout
+    Json,
+    /// A SARIF 2.1.0 log for code-scanning tools, written to stdout
+    Sarif,
+    /// Checkstyle XML, written to stdout
+    Checkstyle,
+    /// JUnit XML with one testcase per file, written to stdout
+    Junit,
+    /// GitLab Code Quality report, written to stdout
+    Gitlab,
+    /// A self-contained HTML page, written to stdout
+    Html,
+    /// A Markdown summary for pull-request comments, written to stdout
+    Markdown,
+}
+
+impl From<CliSeverity> for lintoko::RuleSeverity {
+    fn from(severity: CliSeverity) -> Self {
+        match severity {
+            CliSeverity::Off => lintoko::RuleSeverity::Off,
+            CliSeverity::Hint => lintoko::RuleSeverity::Hint,
+            CliSeverity::Info => lintoko::RuleSeverity::Info,
+            CliSeverity::Warning => lintoko::RuleSeverity::Warning,
+            CliSeverity::Error => lintoko::RuleSeverity::Error,
+        }
+    }
+}
+
+impl From<OverrideSeverity> for lintoko::RuleSeverity {
+    fn from(severity: OverrideSeverity) -> Self {
+        match severity {
+            OverrideSeverity::Hint => lintoko::RuleSeverity::Hint,
+            OverrideSeverity::Info => lintoko::RuleSeverity::Info,
+            OverrideSeverity::Warning => lintoko::RuleSeverity::Warning,
+            OverrideSeverity::Error => lintoko::RuleSeverity::Error,
+        }
+    }
+}
+
+impl From<OutputFormat> for lintoko::OutputFormat {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Pretty => lintoko::OutputFormat::Pretty,
+            OutputFormat::Text => lintoko::OutputFormat::Text,
+            OutputFormat::Compact => lintoko::OutputFormat::Compact,
+            OutputFormat::Github => lintoko::OutputFormat::Github,
+            OutputFormat::Json => lintoko::OutputFormat::Json,
+            OutputFormat::Sarif => lintoko::OutputFormat::Sarif,
+            OutputFormat::Checkstyle => lintoko::OutputFormat::Checkstyle,
+            OutputFormat::Junit => lintoko::OutputFormat::Junit,
+            OutputFormat::Gitlab => lintoko::OutputFormat::Gitlab,
+            OutputFormat::Html => lintoko::OutputFormat::Html,
+            OutputFormat::Markdown => lintoko::OutputFormat::Markdown,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FormatArg {
+    format: lintoko::OutputFormat,
+    path: Option<PathBuf>,
+}
+
+fn parse_format_arg(arg: &str) -> Result<FormatArg, String> {
+    let (name, path) = match arg.split_once('=') {
+        Some((name, path)) => (name, Some(PathBuf::from(path))),
+        None => (arg, None),
+    };
+    let format = OutputFormat::from_str(name, true).map_err(|_| {
+        let possible: Vec<_> = OutputFormat::value_variants()
+            .iter()
+            .filter_map(|f| f.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        format!(
+            "unknown format '{name}', possible values: {}",
+            possible.join(", ")
+        )
+    })?;
+    Ok(FormatArg {
+        format: format.into(),
+        path,
+    })
+}
+
+fn parse_rule_severity(arg: &str) -> Result<(String, lintoko::RuleSeverity), String> {
+    let (rule, severity) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected RULE=SEVERITY, got '{arg}'"))?;
+    let severity = CliSeverity::from_str(severity, true).map_err(|_| {
+        format!("unknown severity '{severity}', possible values: off, hint, info, warning, error")
+    })?;
+    Ok((rule.to_string(), severity.into()))
 }
 
 /// Expands passed input parameters (skips hidden directories, unless explicitly referenced)
@@ -80,7 +298,63 @@ fn expand_input(input: &String) -> Vec<PathBuf> {
     }
 }
 
-fn main() -> Result<()> {
+/// Expands all inputs, defaulting to every Motoko file underneath the current directory
+fn expand_inputs(inputs: &[String]) -> Result<BTreeSet<PathBuf>> {
+    let inputs = if inputs.is_empty() {
+        &["**/*.mo".to_string()][..]
+    } else {
+        inputs
+    };
+    // Collecting into a Set here to guarantee we only lint every file once.
+    let all_files: BTreeSet<PathBuf> = inputs.iter().flat_map(expand_input).collect();
+    if all_files.is_empty() {
+        bail!("Input patterns did not match any files")
+    }
+    Ok(all_files)
+}
+
+/// Inputs in `lintoko.toml` are relative to the directory containing it
+fn resolve_project_inputs(dir: &Path, inputs: &[String]) -> Result<Vec<String>> {
+    let cwd = std::env::current_dir()?.canonicalize()?;
+    Ok(inputs
+        .iter()
+        .map(|input| match cwd.strip_prefix(dir) {
+            Ok(subdir) if subdir.as_os_str().is_empty() => input.clone(),
+            // Keep the paths relative when linting from a subdirectory of the project
+            Ok(subdir) => {
+                let up: PathBuf = subdir.components().map(|_| "..").collect();
+                up.join(input).to_string_lossy().into_owned()
+            }
+            Err(_) => dir.join(input).to_string_lossy().into_owned(),
+        })
+        .collect())
+}
+
+fn list_suppressions(config: &lintoko::Config, inputs: &[String]) -> Result<()> {
+    let today = config.today();
+    let mut suppressions = vec![];
+    for input in expand_inputs(inputs).context(ConfigError)? {
+        let file_content = std::fs::read_to_string(&input)
+            .with_context(|| anyhow!("Failed to read file at '{}'", input.display()))?;
+        for s in lintoko::suppressions(&file_content) {
+            suppressions.push(serde_json::json!({
+                "path": input.to_string_lossy(),
+                "line": s.range.start_point.row + 1,
+                "kind": s.kind,
+                "rules": s.rules,
+                "reason": s.reason,
+                "until": s.until.map(|d| d.to_string()),
+                "expired": s.is_expired(today),
+            }));
+        }
+    }
+    let mut out = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, &suppressions)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
     let filter = if args.verbose {
         LevelFilter::DEBUG
@@ -95,71 +369,503 @@ fn main() -> Result<()> {
 
     let config = lintoko::Config {
         fix: args.fix,
-        format: match args.format {
-            OutputFormat::Pretty => lintoko::OutputFormat::Pretty,
-            OutputFormat::Text => lintoko::OutputFormat::Text,
-        },
-        severity_override: args.severity.map(|s| match s {
-            CliSeverity::Warning => lintoko::RuleSeverity::Warning,
-            CliSeverity::Error => lintoko::RuleSeverity::Error,
-        }),
+        severity_override: args.severity.map(Into::into),
+        end_positions: args.end_positions,
+        max_findings: args.max_findings,
+        audit_suppressions: args.audit_suppressions,
+        ..lintoko::Config::default()
+    };
+    let result = match &args.command {
+        Some(Command::Suppressions { inputs }) => {
+            list_suppressions(&config, inputs).map(|()| ExitCode::SUCCESS)
+        }
+        None => run(&args, config),
     };
+    result.unwrap_or_else(|err| {
+        eprintln!("Error: {err:?}");
+        if err.downcast_ref::<ConfigError>().is_some() {
+            ExitCode::from(EXIT_CONFIG_ERROR)
+        } else {
+            ExitCode::from(EXIT_INTERNAL_ERROR)
+        }
+    })
+}
+
+fn run(args: &Args, config: lintoko::Config) -> Result<ExitCode> {
+    if args.watch {
+        return watch::watch(args, config);
+    }
 
-    let inputs = if args.inputs.is_empty() {
-        vec!["**/*.mo".to_string()]
+    let mut setup = Setup::load(args)?;
+    let all_files = expand_inputs(&setup.inputs).context(ConfigError)?;
+    setup.resolve_all(&all_files).context(ConfigError)?;
+    let Setup {
+        mut resolver,
+        cwd_config,
+        ..
+    } = setup;
+
+    let formats = if args.formats.is_empty() {
+        vec![FormatArg {
+            format: cwd_config.format().unwrap_or_default(),
+            path: None,
+        }]
     } else {
-        args.inputs
+        args.formats.clone()
     };
-    // Collecting into a Set here to guarantee we only lint every file once.
-    let all_files: BTreeSet<PathBuf> = inputs.iter().flat_map(expand_input).collect();
-    if all_files.is_empty() {
-        bail!("Input patterns did not match any files")
+    if args.output.is_some() && formats.iter().filter(|f| f.path.is_none()).count() > 1 {
+        return Err(anyhow!(
+            "`--output` is ambiguous with multiple formats, use `--format FORMAT=PATH` instead"
+        )
+        .context(ConfigError));
     }
-
-    let mut rules = vec![];
-    for dir in &args.rules {
-        if dir.is_file() {
-            debug!("Loading single rule from: {}", dir.display());
-            rules = vec![lintoko::load_rule_from_file(dir)?];
-            break;
-        }
-        debug!("Loading rules from: {}", dir.display());
-        rules.extend(lintoko::load_rules_from_directory(dir)?);
+    let mut reporters = vec![];
+    for FormatArg { format, path } in &formats {
+        let format = *format;
+        let out: Box<dyn Write> = match path.as_ref().or(args.output.as_ref()) {
+            Some(path) => Box::new(BufWriter::new(fs::File::create(path).with_context(
+                || anyhow!("Failed to create output file at '{}'", path.display()),
+            )?)),
+            None if format.is_aggregated() => Box::new(std::io::stdout()),
+            None => Box::new(std::io::stderr()),
+        };
+        let config = lintoko::Config {
+            format,
+            ..config.clone()
+        };
+        reporters.push(lintoko::Reporter::new(config, out));
     }
 
+    let mut baseline = args
+        .baseline
+        .as_deref()
+        .map(lintoko::Baseline::load)
+        .transpose()
+        .context(ConfigError)?;
+    let mut new_baseline = lintoko::Baseline::default();
+    // Baseline paths don't depend on the working directory: they are relative to the project root, or to
+    // the baseline file if there's no configuration
+    let baseline_root = cwd_config.root().map(Path::to_path_buf).or_else(|| {
+        let file = args.baseline.as_ref().or(args.write_baseline.as_ref())?;
+        let dir = file.parent().filter(|d| !d.as_os_str().is_empty());
+        dir.unwrap_or(Path::new(".")).canonicalize().ok()
+    });
+
     let mut error_count = 0;
     let mut warning_count = 0;
+    let mut linters = Linters::new(&resolver);
+    let mut jobs = vec![];
     for input in all_files {
-        debug!("Linting file: {}", input.display());
+        let resolved = resolver.resolve(&input).context(ConfigError)?;
+        let absolute = input.canonicalize().unwrap_or_else(|_| input.clone());
+        if !resolved.includes_file(&absolute) {
+            debug!(
+                "Skipping file excluded by the configuration: {}",
+                input.display()
+            );
+            continue;
+        }
+        let linter = linters.index(&resolved);
+        jobs.push(Job {
+            input,
+            absolute,
+            resolved,
+            linter,
+        });
+    }
+    let mut tool_diagnostics = linters.rule_errors.clone();
+    let cache_dir = cwd_config
+        .cache_dir()
+        .unwrap_or_else(|| PathBuf::from(lintoko::CACHE_DIR));
+    if args.clear_cache {
+        lintoko::Cache::clear(&cache_dir)?;
+    }
+    let cache = if !args.no_cache && (args.cache || cwd_config.cache() == Some(true)) {
+        // Linting still works without the cache, just slower
+        lintoko::Cache::open(&cache_dir)
+            .inspect_err(|err| warn!("Not using the cache: {err:#}"))
+            .ok()
+    } else {
+        None
+    };
+    let threads = args.jobs.map_or_else(
+        || std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        NonZeroUsize::get,
+    );
 
-        let file_content = std::fs::read_to_string(&input)
-            .with_context(|| anyhow!("Failed to read file at '{}'", input.display()))?;
+    let cache = cache.as_ref();
+    lint_files(
+        &config,
+        &jobs,
+        &linters.linters,
+        cache,
+        threads,
+        |job, outcome| {
+            let path = job.input.to_string_lossy();
+            let (file_content, mut res) = match outcome {
+                FileOutcome::Linted { content, result } => (content, result),
+                FileOutcome::Unreadable(message) => {
+                    tool_diagnostics.push(lintoko::ToolDiagnostic {
+                        path: Some(path.to_string()),
+                        rule: None,
+                        message,
+                    });
+                    return Ok(());
+                }
+            };
+            tool_diagnostics.append(&mut res.tool_diagnostics);
+            let baseline_path = baseline_path(baseline_root.as_deref(), job);
+            if args.write_baseline.is_some() {
+                new_baseline.record(&baseline_path, &file_content, &res.diagnostics);
+            }
+            if let Some(baseline) = &mut baseline {
+                baseline.apply(&baseline_path, &file_content, &mut res);
+            }
+            for reporter in &mut reporters {
+                reporter.add(&path, &file_content, &res)?;
+            }
+            if res.fix_overlaps {
+                eprintln!("{}", lintoko::FIX_OVERLAPS_MESSAGE);
+            }
+            error_count += res.error_count;
+            warning_count += res.warning_count;
+            // Fixes are written here rather than on the worker threads, so every file is only written once
+            if let Some(fixed_file) = res.fixed_file {
+                debug!("Writing fixed file: {}", job.input.display());
+                fs::write(&job.input, fixed_file)?
+            }
+            Ok(())
+        },
+    )?;
+    if let Some(cache) = cache {
+        debug!(
+            "Replayed {} of {} files from the cache",
+            cache.hits(),
+            jobs.len()
+        );
+    }
+    for name in resolver.unknown_rules() {
+        match lintoko::did_you_mean(&name, resolver.known_rules()) {
+            Some(suggestion) => warn!(
+                "{} configures unknown rule '{name}'. Did you mean '{suggestion}'?",
+                lintoko::PROJECT_CONFIG_FILE
+            ),
+            None => warn!(
+                "{} configures unknown rule '{name}'",
+                lintoko::PROJECT_CONFIG_FILE
+            ),
+        }
+    }
+    let all_rules = resolver.all_rules();
+    for reporter in reporters {
+        reporter.finish(&all_rules)?;
+    }
 
-        let res = lintoko::lint_file(
-            &config,
-            input.to_string_lossy().as_ref(),
-            &file_content,
-            &rules,
-            std::io::stderr(),
-        )?;
-        error_count += res.error_count;
-        warning_count += res.warning_count;
-        if let Some(fixed_file) = res.fixed_file {
-            debug!("Writing fixed file: {}", input.display());
-            fs::write(&input, fixed_file)?
+    if let Some(baseline) = &baseline {
+        let fixed = baseline.fixed();
+        if !fixed.is_empty() {
+            eprintln!(
+                "{} baseline entries have been fixed, re-run with `--write-baseline` to drop them:",
+                fixed.len()
+            );
+            for (entry, count) in fixed {
+                eprintln!(
+                    "  {}: {} `{}` ({count}x)",
+                    entry.path, entry.rule, entry.excerpt
+                );
+            }
         }
     }
+    let tool_exit_code = report_tool_diagnostics(&tool_diagnostics);
+    if let Some(path) = &args.write_baseline {
+        let file = fs::File::create(path)
+            .with_context(|| anyhow!("Failed to create baseline at '{}'", path.display()))?;
+        new_baseline.write(BufWriter::new(file))?;
+        eprintln!(
+            "Wrote {} findings to the baseline at '{}'",
+            new_baseline.len(),
+            path.display()
+        );
+        return Ok(ExitCode::from(tool_exit_code.unwrap_or(0)));
+    }
 
-    if error_count > 0 {
+    let max_warnings = if args.deny_warnings {
+        Some(0)
+    } else {
+        args.max_warnings
+    };
+    let lint_exit_code = if error_count > 0 {
         if warning_count > 0 {
-            bail!("Found {error_count} errors and {warning_count} warnings")
+            eprintln!("Error: Found {error_count} errors and {warning_count} warnings");
         } else {
-            bail!("Found {error_count} errors")
+            eprintln!("Error: Found {error_count} errors");
         }
-    } else if warning_count > 0 {
-        eprintln!("Found {warning_count} warnings");
-        Ok(())
+        EXIT_LINT_FAILURES
+    } else if let Some(max) = max_warnings
+        && warning_count > max
+    {
+        if max == 0 {
+            eprintln!("Error: Found {warning_count} warnings");
+        } else {
+            eprintln!("Error: Found {warning_count} warnings, more than the maximum of {max}");
+        }
+        EXIT_LINT_FAILURES
     } else {
+        if warning_count > 0 {
+            eprintln!("Found {warning_count} warnings");
+        }
+        0
+    };
+    // Failures of the tool itself take precedence, as they mean the findings are incomplete
+    Ok(ExitCode::from(
+        tool_exit_code.map_or(lint_exit_code, |code| code.max(lint_exit_code)),
+    ))
+}
+
+/// The rules and configuration files of a run. Watch mode loads them again whenever they change
+struct Setup {
+    resolver: lintoko::ConfigResolver,
+    selection: lintoko::RuleSelection,
+    cwd_config: Arc<lintoko::ResolvedConfig>,
+    /// The inputs passed on the command line, or the closest `inputs`
+    inputs: Vec<String>,
+}
+
+impl Setup {
+    fn load(args: &Args) -> Result<Self> {
+        let cli_rules = if args.rules.is_empty() {
+            None
+        } else {
+            let mut rules = vec![];
+            for dir in &args.rules {
+                if dir.is_file() {
+                    debug!("Loading single rule from: {}", dir.display());
+                    rules = vec![lintoko::load_rule_from_file(dir).context(ConfigError)?];
+                    break;
+                }
+                debug!("Loading rules from: {}", dir.display());
+                rules.extend(lintoko::load_rules_from_directory(dir).context(ConfigError)?);
+            }
+            Some(rules)
+        };
+        let selection = lintoko::RuleSelection {
+            severities: args.rule_severity.clone(),
+            disabled: args.disable.clone(),
+            only: args.only.clone(),
+        };
+        let mut resolver =
+            lintoko::ConfigResolver::new(cli_rules).with_selection(selection.clone());
+        let cwd_config = resolver
+            .resolve_dir(&std::env::current_dir()?)
+            .context(ConfigError)?;
+        let inputs = match cwd_config.inputs() {
+            Some((dir, inputs)) if args.inputs.is_empty() => resolve_project_inputs(dir, inputs)?,
+            _ => args.inputs.clone(),
+        };
+        Ok(Self {
+            resolver,
+            selection,
+            cwd_config,
+            inputs,
+        })
+    }
+
+    /// Resolves the configuration of all `files` up front, to know all rule names before reporting anything
+    fn resolve_all(&mut self, files: &BTreeSet<PathBuf>) -> Result<()> {
+        for file in files {
+            self.resolver.resolve(file)?;
+        }
+        self.selection.validate(&self.resolver.known_rules())
+    }
+}
+
+/// One linter per distinct configuration, so every query is only compiled once
+struct Linters {
+    configs: Vec<Arc<lintoko::ResolvedConfig>>,
+    linters: Vec<lintoko::Linter>,
+    /// The rules that failed to compile in any of the linters
+    rule_errors: Vec<lintoko::ToolDiagnostic>,
+    /// All loaded rules, including the ones disabled for some or all files, for the suppression audit
+    known_rules: Vec<String>,
+    /// Linters of previously loaded configurations by the fingerprints of their rules, see `reuse`
+    previous: Vec<(Vec<String>, lintoko::Linter)>,
+}
+
+fn fingerprints(rules: &[lintoko::Rule]) -> Vec<String> {
+    rules.iter().map(lintoko::Rule::fingerprint).collect()
+}
+
+impl Linters {
+    fn new(resolver: &lintoko::ConfigResolver) -> Self {
+        Self {
+            configs: vec![],
+            linters: vec![],
+            rule_errors: vec![],
+            known_rules: resolver
+                .known_rules()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            previous: vec![],
+        }
+    }
+
+    /// Keeps the linters of `old`, so configurations with the same rules don't compile their queries again
+    fn reuse(&mut self, old: Linters) {
+        for (config, linter) in old.configs.iter().zip(old.linters) {
+            self.previous.push((fingerprints(config.rules()), linter));
+        }
+    }
+
+    /// The index of the linter for `resolved`, which is created on first use
+    fn index(&mut self, resolved: &Arc<lintoko::ResolvedConfig>) -> usize {
+        if let Some(i) = self.configs.iter().position(|c| Arc::ptr_eq(c, resolved)) {
+            return i;
+        }
+        let reused = match self.previous.as_slice() {
+            [] => None,
+            previous => {
+                let fingerprints = fingerprints(resolved.rules());
+                previous
+                    .iter()
+                    .position(|(f, _)| *f == fingerprints)
+                    .map(|i| self.previous.swap_remove(i).1)
+            }
+        };
+        let linter = reused
+            .unwrap_or_else(|| lintoko::Linter::new(resolved.rules()))
+            .with_known_rules(self.known_rules.clone());
+        self.rule_errors.extend_from_slice(linter.rule_errors());
+        self.configs.push(resolved.clone());
+        self.linters.push(linter);
+        self.linters.len() - 1
+    }
+}
+
+/// A file to lint, with everything resolved up front so files can be linted in any order
+struct Job {
+    input: PathBuf,
+    absolute: PathBuf,
+    resolved: Arc<lintoko::ResolvedConfig>,
+    /// Index of the linter for the file's configuration
+    linter: usize,
+}
+
+/// The path of the job's file in the baseline, relative to `root`
+fn baseline_path(root: Option<&Path>, job: &Job) -> String {
+    root.and_then(|root| job.absolute.strip_prefix(root).ok())
+        .unwrap_or(&job.input)
+        .to_string_lossy()
+        .into_owned()
+}
+
+enum FileOutcome {
+    Linted {
+        content: String,
+        result: lintoko::LintResult,
+    },
+    Unreadable(String),
+}
+
+fn lint_job(
+    config: &lintoko::Config,
+    job: &Job,
+    linter: &mut lintoko::Linter,
+    cache: Option<&lintoko::Cache>,
+) -> Result<FileOutcome> {
+    debug!("Linting file: {}", job.input.display());
+    let content = match fs::read_to_string(&job.input) {
+        Ok(content) => content,
+        Err(err) => {
+            return Ok(FileOutcome::Unreadable(format!(
+                "Failed to read file: {err}"
+            )));
+        }
+    };
+    let path = job.input.to_string_lossy();
+    let in_scope = |rule: &lintoko::Rule| job.resolved.in_scope(rule, &job.absolute);
+    let result = match cache {
+        Some(cache) => cache.lint(linter, config, &path, &content, in_scope)?,
+        None => linter.lint_filtered(config, &path, &content, in_scope)?,
+    };
+    Ok(FileOutcome::Linted { content, result })
+}
+
+/// Lints all `jobs` on `threads` threads. `emit` is called on the current thread, once per job and in the
+/// order of `jobs`, so the output doesn't depend on which thread finishes first
+fn lint_files(
+    config: &lintoko::Config,
+    jobs: &[Job],
+    linters: &[lintoko::Linter],
+    cache: Option<&lintoko::Cache>,
+    threads: usize,
+    mut emit: impl FnMut(&Job, FileOutcome) -> Result<()>,
+) -> Result<()> {
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..threads.min(jobs.len()) {
+            let sender = sender.clone();
+            let next = &next;
+            // Every thread gets its own parsers, the compiled queries are shared
+            let mut linters = linters.to_vec();
+            scope.spawn(move || {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(job) = jobs.get(i) else { break };
+                    let outcome = lint_job(config, job, &mut linters[job.linter], cache);
+                    // The receiver is gone when emitting failed, so there's no point in linting any further
+                    if sender.send((i, outcome)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        let mut pending = BTreeMap::new();
+        let mut emitted = 0;
+        for (i, outcome) in receiver {
+            pending.insert(i, outcome);
+            while let Some(outcome) = pending.remove(&emitted) {
+                emit(&jobs[emitted], outcome?)?;
+                emitted += 1;
+            }
+        }
         Ok(())
+    })
+}
+
+/// Prints the rules and files that couldn't be linted, and returns the exit code for them
+fn report_tool_diagnostics(tool_diagnostics: &[lintoko::ToolDiagnostic]) -> Option<u8> {
+    if tool_diagnostics.is_empty() {
+        return None;
+    }
+    // A broken rule usually fails the same way on every file, so only list it once
+    let mut grouped: BTreeMap<(Option<&str>, &str), BTreeSet<&str>> = BTreeMap::new();
+    for d in tool_diagnostics {
+        let paths = grouped.entry((d.rule.as_deref(), &d.message)).or_default();
+        paths.extend(d.path.as_deref());
+    }
+    eprintln!(
+        "Error: {} failures, the results above are incomplete:",
+        grouped.len()
+    );
+    for ((rule, message), paths) in &grouped {
+        let location = match paths.len() {
+            0 => None,
+            1 => paths.first().map(|path| path.to_string()),
+            n => Some(format!("{n} files")),
+        };
+        match (rule, location) {
+            (Some(rule), Some(location)) => eprintln!("  rule '{rule}' in {location}: {message}"),
+            (Some(rule), None) => eprintln!("  rule '{rule}': {message}"),
+            (None, location) => eprintln!("  {}: {message}", location.unwrap_or_default()),
+        }
+    }
+    // Unreadable files are I/O errors, anything else points to a broken rule
+    if tool_diagnostics.iter().any(|d| d.rule.is_none()) {
+        Some(EXIT_INTERNAL_ERROR)
+    } else {
+        Some(EXIT_CONFIG_ERROR)
     }
 }
@@ -0,0 +1,188 @@
+use crate::report::Report;
+use crate::{Config, snippet};
+use anyhow::Result;
+use std::io::Write;
+
+fn longest_backtick_run(s: &str) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for c in s.chars() {
+        current = if c == '`' { current + 1 } else { 0 };
+        longest = longest.max(current);
+    }
+    longest
+}
+
+// The fence has to be longer than any run of backticks inside the code block
+fn fence(code: &str) -> String {
+    "`".repeat(longest_backtick_run(code).max(2) + 1)
+}
+
+// Inline code, delimited by more backticks than it contains
+fn code_span(s: &str) -> String {
+    let delimiter = "`".repeat(longest_backtick_run(s) + 1);
+    let padding = if s.starts_with('`') || s.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{delimiter}{padding}{s}{padding}{delimiter}")
+}
+
+// Table cells end at any `|`, even inside inline code
+fn table_cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+/// Escapes text so that it renders literally, on a single line
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push(' '),
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#' | '~' | '!' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes the report as Markdown, suitable for posting as a pull-request comment
+pub(crate) fn write(report: &Report, config: &Config, mut out: impl Write) -> Result<()> {
+    let per_rule = report.per_rule();
+    let total: usize = per_rule.values().sum();
+
+    writeln!(out, "## lintoko")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Found {} errors, {} warnings, {} infos and {} hints in {} files.",
+        report.error_count,
+        report.warning_count,
+        report.info_count,
+        report.hint_count,
+        report.files.len()
+    )?;
+    if total == 0 {
+        return Ok(());
+    }
+    writeln!(out)?;
+    writeln!(out, "<details>")?;
+    writeln!(out, "<summary>Summary</summary>")?;
+    writeln!(out)?;
+    writeln!(out, "| Rule | Severity | Count |")?;
+    writeln!(out, "| --- | --- | --- |")?;
+    for ((rule, severity), count) in &per_rule {
+        writeln!(
+            out,
+            "| {} | {severity} | {count} |",
+            table_cell(&code_span(rule))
+        )?;
+    }
+    writeln!(out)?;
+    writeln!(out, "</details>")?;
+
+    let mut remaining = config.max_findings.unwrap_or(usize::MAX);
+    for file in report.files.iter().filter(|f| !f.diagnostics.is_empty()) {
+        if remaining == 0 {
+            break;
+        }
+        writeln!(out)?;
+        writeln!(out, "### {}", code_span(&file.path))?;
+        writeln!(out)?;
+        for d in file.diagnostics.iter().take(remaining) {
+            let code = snippet(&file.source, d);
+            let fence = fence(&code);
+            writeln!(
+                out,
+                "- **{}** {} at line {}, column {}: {}",
+                d.severity,
+                code_span(&d.rule),
+                d.range.start_point.row + 1,
+                d.range.start_point.column + 1,
+                escape(&d.description),
+            )?;
+            writeln!(out, "  {fence}motoko")?;
+            for line in code.lines() {
+                writeln!(out, "  {line}")?;
+            }
+            writeln!(out, "  {fence}")?;
+            remaining -= 1;
+        }
+    }
+    if let Some(max) = config.max_findings
+        && total > max
+    {
+        writeln!(out)?;
+        writeln!(out, "_... and {} more findings not listed._", total - max)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::OutputFormat;
+    use crate::report::test::{pun_fields, render};
+
+    fn markdown(max_findings: Option<usize>) -> String {
+        let config = Config {
+            format: OutputFormat::Markdown,
+            max_findings,
+            ..Config::default()
+        };
+        let files = [("a.mo", "{ x = x }"), ("b.mo", "{ y = y; z = z }")];
+        String::from_utf8(render(&config, &pun_fields(), &files)).unwrap()
+    }
+
+    #[test]
+    fn it_writes_summary_and_findings() {
+        let md = markdown(None);
+        assert!(
+            md.contains("Found 0 errors, 3 warnings, 0 infos and 0 hints in 2 files."),
+            "{md}"
+        );
+        assert!(md.contains("| `pun-fields` | warning | 3 |"), "{md}");
+        assert!(md.contains("### `a.mo`"), "{md}");
+        assert!(
+            md.contains("- **warning** `pun-fields` at line 1, column 3: "),
+            "{md}"
+        );
+        assert!(md.contains("  ```motoko\n  1 { x = x }\n  ```\n"), "{md}");
+        assert!(!md.contains("more findings not listed"), "{md}");
+    }
+
+    #[test]
+    fn it_caps_the_number_of_findings() {
+        let md = markdown(Some(1));
+        // The summary still counts everything
+        assert!(md.contains("| `pun-fields` | warning | 3 |"), "{md}");
+        assert_eq!(md.matches("- **warning**").count(), 1, "{md}");
+        assert!(!md.contains("### `b.mo`"), "{md}");
+        assert!(md.contains("_... and 2 more findings not listed._"), "{md}");
+    }
+
+    #[test]
+    fn fences_are_longer_than_backtick_runs() {
+        assert_eq!(fence("let x = 1"), "```");
+        assert_eq!(fence("a ```` b"), "`````");
+    }
+
+    #[test]
+    fn it_escapes_paths_and_descriptions() {
+        let config = Config {
+            format: OutputFormat::Markdown,
+            ..Config::default()
+        };
+        let files = [("a`b|c.mo", "{ x = x }")];
+        let md = String::from_utf8(render(&config, &pun_fields(), &files)).unwrap();
+        assert!(md.contains("### ``a`b|c.mo``\n"), "{md}");
+        assert!(md.contains(r"Replace \`{ x = x }\` with \`{ x }\`"), "{md}");
+        assert_eq!(code_span("`a`"), "`` `a` ``");
+        assert_eq!(table_cell(&code_span("a|b")), "`a\\|b`");
+        assert_eq!(escape("a | *b*\nc"), "a \\| \\*b\\* c");
+    }
+}
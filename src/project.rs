@@ -0,0 +1,583 @@
+use crate::{
+    CACHE_DIR, OutputFormat, Rule, RuleSelection, RuleSeverity, deserialize_globs,
+    load_rule_from_file, load_rules_from_directory,
+};
+use anyhow::{Context, Result, anyhow};
+use glob::Pattern;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::debug;
+
+pub const PROJECT_CONFIG_FILE: &str = "lintoko.toml";
+
+/// Overrides for a single rule, from a `[rule.<name>]` section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleOverride {
+    pub severity: Option<RuleSeverity>,
+    /// Whether the rule runs at all. Defaults to `true`
+    pub enabled: Option<bool>,
+    // Narrow down the paths the rule applies to, on top of the rule's own `includes` / `excludes`.
+    #[serde(default, deserialize_with = "deserialize_globs")]
+    includes: Vec<Pattern>,
+    #[serde(default, deserialize_with = "deserialize_globs")]
+    excludes: Vec<Pattern>,
+}
+
+/// The contents of a single `lintoko.toml`. Paths and globs are relative to the directory containing it
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// The directory containing the configuration file
+    #[serde(skip)]
+    pub dir: PathBuf,
+    /// Stops looking for configuration files in parent directories
+    #[serde(default)]
+    pub root: bool,
+    /// Rule directories or single rule files
+    #[serde(default)]
+    pub rules: Vec<PathBuf>,
+    /// Files, directories, or globs to lint when none are passed on the command line
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    pub format: Option<OutputFormat>,
+    /// Keeps the results of unchanged files in `.lintoko-cache` next to this file
+    pub cache: Option<bool>,
+    // Files outside `includes` or inside `excludes` aren't linted at all.
+    #[serde(default, deserialize_with = "deserialize_globs")]
+    includes: Vec<Pattern>,
+    #[serde(default, deserialize_with = "deserialize_globs")]
+    excludes: Vec<Pattern>,
+    #[serde(default, rename = "rule")]
+    pub overrides: BTreeMap<String, RuleOverride>,
+}
+
+fn matches_globs(includes: &[Pattern], excludes: &[Pattern], path: &str) -> bool {
+    let matches_any = |pats: &[Pattern]| pats.iter().any(|p| p.matches(path));
+    (includes.is_empty() || matches_any(includes)) && !matches_any(excludes)
+}
+
+impl ProjectConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| anyhow!("Failed to read configuration from '{}'", path.display()))?;
+        let mut config: Self = toml::from_str(&content)
+            .with_context(|| anyhow!("Failed to parse configuration from '{}'", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        config.dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        config.rules = config.rules.iter().map(|r| config.dir.join(r)).collect();
+        Ok(config)
+    }
+
+    /// The path of `file` relative to this configuration's directory, which is what its globs match against
+    fn relative_path(&self, file: &Path) -> String {
+        let relative = file.strip_prefix(&self.dir).unwrap_or(file);
+        relative.to_string_lossy().replace('\\', "/")
+    }
+}
+
+/// The effective configuration for the files in one directory, combined from all `lintoko.toml` files
+/// in it and its parents. Closer files extend or override the settings of the ones further up.
+#[derive(Debug, Default)]
+pub struct ResolvedConfig {
+    /// Outermost first
+    layers: Vec<Arc<ProjectConfig>>,
+    /// All rules for the directory, with the severity and enabled overrides applied
+    rules: Vec<Rule>,
+}
+
+impl ResolvedConfig {
+    /// The rules for the directory, before path-scoped overrides are applied. See `rules_for`
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// The closest `inputs`, together with the directory they are relative to
+    pub fn inputs(&self) -> Option<(&Path, &[String])> {
+        self.layers
+            .iter()
+            .rev()
+            .find(|l| !l.inputs.is_empty())
+            .map(|l| (l.dir.as_path(), &l.inputs[..]))
+    }
+
+    /// The closest `format`
+    pub fn format(&self) -> Option<OutputFormat> {
+        self.layers.iter().rev().find_map(|l| l.format)
+    }
+
+    /// The closest `cache` setting
+    pub fn cache(&self) -> Option<bool> {
+        self.layers.iter().rev().find_map(|l| l.cache)
+    }
+
+    /// The directory of the closest configuration file, if there is one
+    pub fn root(&self) -> Option<&Path> {
+        self.layers.last().map(|l| l.dir.as_path())
+    }
+
+    /// Where the result cache is kept: next to the closest configuration file, if there is one
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        self.root().map(|root| root.join(CACHE_DIR))
+    }
+
+    /// Whether the file at the absolute path `file` should be linted at all
+    pub fn includes_file(&self, file: &Path) -> bool {
+        self.layers
+            .iter()
+            .all(|l| matches_globs(&l.includes, &l.excludes, &l.relative_path(file)))
+    }
+
+    /// Whether the path-scoped overrides let `rule` run on the file at the absolute path `file`
+    pub fn in_scope(&self, rule: &Rule, file: &Path) -> bool {
+        self.layers.iter().all(|l| {
+            l.overrides
+                .get(&rule.name)
+                .is_none_or(|o| matches_globs(&o.includes, &o.excludes, &l.relative_path(file)))
+        })
+    }
+
+    /// The rules to run on the file at the absolute path `file`
+    pub fn rules_for(&self, file: &Path) -> Cow<'_, [Rule]> {
+        let in_scope = |rule: &Rule| self.in_scope(rule, file);
+        if self.rules.iter().all(in_scope) {
+            Cow::Borrowed(&self.rules)
+        } else {
+            Cow::Owned(self.rules.iter().filter(|r| in_scope(r)).cloned().collect())
+        }
+    }
+}
+
+/// Finds the configuration for every linted file. Configuration files, rule directories and the
+/// resulting rule sets are only loaded once, and shared between all directories with the same chain
+/// of configuration files.
+#[derive(Debug, Default)]
+pub struct ConfigResolver {
+    /// Rules passed on the command line, which replace the rule sources of all configuration files
+    cli_rules: Option<Vec<Rule>>,
+    selection: RuleSelection,
+    /// The configuration file in each directory, if there is one
+    files: HashMap<PathBuf, Option<Arc<ProjectConfig>>>,
+    /// Keyed by the directories of the configuration files in the chain
+    chains: HashMap<Vec<PathBuf>, Arc<ResolvedConfig>>,
+    dirs: HashMap<PathBuf, Arc<ResolvedConfig>>,
+    sources: HashMap<PathBuf, Vec<Rule>>,
+}
+
+impl ConfigResolver {
+    pub fn new(cli_rules: Option<Vec<Rule>>) -> Self {
+        Self {
+            cli_rules,
+            ..Self::default()
+        }
+    }
+
+    /// Applies `selection` on top of every configuration
+    pub fn with_selection(self, selection: RuleSelection) -> Self {
+        Self { selection, ..self }
+    }
+
+    fn config_in(&mut self, dir: &Path) -> Result<Option<Arc<ProjectConfig>>> {
+        if let Some(config) = self.files.get(dir) {
+            return Ok(config.clone());
+        }
+        let path = dir.join(PROJECT_CONFIG_FILE);
+        let config = if path.is_file() {
+            debug!("Using configuration at: {}", path.display());
+            Some(Arc::new(ProjectConfig::load(&path)?))
+        } else {
+            None
+        };
+        self.files.insert(dir.to_path_buf(), config.clone());
+        Ok(config)
+    }
+
+    fn load_source(&mut self, source: &Path) -> Result<&[Rule]> {
+        if !self.sources.contains_key(source) {
+            let rules = if source.is_file() {
+                debug!("Loading single rule from: {}", source.display());
+                vec![load_rule_from_file(source)?]
+            } else {
+                debug!("Loading rules from: {}", source.display());
+                load_rules_from_directory(source)?
+            };
+            self.sources.insert(source.to_path_buf(), rules);
+        }
+        Ok(&self.sources[source])
+    }
+
+    fn resolve_chain(&mut self, layers: Vec<Arc<ProjectConfig>>) -> Result<Arc<ResolvedConfig>> {
+        let key: Vec<PathBuf> = layers.iter().map(|l| l.dir.clone()).collect();
+        if let Some(resolved) = self.chains.get(&key) {
+            return Ok(resolved.clone());
+        }
+        let mut rules: Vec<Rule> = match &self.cli_rules {
+            Some(rules) => rules.clone(),
+            None => {
+                let mut rules: Vec<Rule> = vec![];
+                for source in layers.iter().flat_map(|l| &l.rules) {
+                    for rule in self.load_source(source)? {
+                        // Rules from closer configuration files replace rules with the same name
+                        match rules.iter_mut().find(|r| r.name == rule.name) {
+                            Some(existing) => *existing = rule.clone(),
+                            None => rules.push(rule.clone()),
+                        }
+                    }
+                }
+                rules
+            }
+        };
+        rules.retain_mut(|rule| {
+            // Like the severity, `enabled` is taken from the closest file that sets it
+            let mut enabled = true;
+            let name = rule.name.clone();
+            for o in layers.iter().filter_map(|l| l.overrides.get(&name)) {
+                if let Some(severity) = o.severity {
+                    rule.pin_severity(severity);
+                }
+                enabled = o.enabled.unwrap_or(enabled);
+            }
+            enabled
+        });
+        self.selection.apply(&mut rules);
+        let resolved = Arc::new(ResolvedConfig { layers, rules });
+        self.chains.insert(key, resolved.clone());
+        Ok(resolved)
+    }
+
+    /// The configuration for files in `dir`
+    pub fn resolve_dir(&mut self, dir: &Path) -> Result<Arc<ResolvedConfig>> {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        if let Some(resolved) = self.dirs.get(&dir) {
+            return Ok(resolved.clone());
+        }
+        let mut layers = vec![];
+        for ancestor in dir.ancestors() {
+            if let Some(config) = self.config_in(ancestor)? {
+                let root = config.root;
+                layers.push(config);
+                if root {
+                    break;
+                }
+            }
+        }
+        layers.reverse();
+        let resolved = self.resolve_chain(layers)?;
+        self.dirs.insert(dir, resolved.clone());
+        Ok(resolved)
+    }
+
+    /// The configuration for `file`
+    pub fn resolve(&mut self, file: &Path) -> Result<Arc<ResolvedConfig>> {
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        self.resolve_dir(file.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// All rules of all configurations resolved so far, e.g. to describe them in reports
+    pub fn all_rules(&self) -> Vec<Rule> {
+        let mut all: Vec<Rule> = self.cli_rules.clone().unwrap_or_default();
+        for resolved in self.chains.values() {
+            for rule in &resolved.rules {
+                if !all.iter().any(|r| r.name == rule.name) {
+                    all.push(rule.clone());
+                }
+            }
+        }
+        all.sort_by(|a, b| a.name.cmp(&b.name));
+        all
+    }
+
+    /// The configuration files and rule sources loaded so far, e.g. to watch them for changes
+    pub fn loaded_paths(&self) -> Vec<PathBuf> {
+        let configs = self
+            .files
+            .iter()
+            .filter(|(_, config)| config.is_some())
+            .map(|(dir, _)| dir.join(PROJECT_CONFIG_FILE));
+        let mut paths: Vec<PathBuf> = configs.chain(self.sources.keys().cloned()).collect();
+        paths.sort();
+        paths
+    }
+
+    /// Where configuration files were looked for but not found so far, e.g. to notice when one is created
+    pub fn missing_configs(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|(_, config)| config.is_none())
+            .map(|(dir, _)| dir.join(PROJECT_CONFIG_FILE))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Names of all rules loaded so far, including disabled ones
+    pub fn known_rules(&self) -> Vec<&str> {
+        let loaded = self
+            .cli_rules
+            .iter()
+            .flatten()
+            .chain(self.sources.values().flatten());
+        let mut known: Vec<&str> = loaded.map(|r| r.name.as_str()).collect();
+        known.sort();
+        known.dedup();
+        known
+    }
+
+    /// Names in `[rule.<name>]` sections that don't match any loaded rule, most likely typos
+    pub fn unknown_rules(&self) -> Vec<String> {
+        let known = self.known_rules();
+        let mut unknown: Vec<String> = self
+            .files
+            .values()
+            .flatten()
+            .flat_map(|config| config.overrides.keys())
+            .filter(|name| !known.contains(&name.as_str()))
+            .cloned()
+            .collect();
+        unknown.sort();
+        unknown.dedup();
+        unknown
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Config, Linter, test_dir};
+    use std::fs;
+
+    fn parse(toml: &str) -> ProjectConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    /// Creates a directory tree under the temp dir, with `files` as (relative path, content) pairs
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = test_dir(name);
+        for (path, content) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir.canonicalize().unwrap()
+    }
+
+    fn names(rules: &[Rule]) -> Vec<&str> {
+        rules.iter().map(|r| r.name()).collect()
+    }
+
+    #[test]
+    fn it_parses_all_sections() {
+        let config = parse(
+            r#"
+            root = true
+            rules = ["lint-rules"]
+            inputs = ["src"]
+            format = "compact"
+            cache = true
+            excludes = ["src/generated/**"]
+
+            [rule.pun-fields]
+            severity = "error"
+
+            [rule.no-let-else]
+            enabled = false
+            "#,
+        );
+        assert!(config.root);
+        assert_eq!(config.rules, vec![PathBuf::from("lint-rules")]);
+        assert_eq!(config.inputs, vec!["src"]);
+        assert_eq!(config.format, Some(OutputFormat::Compact));
+        assert_eq!(config.cache, Some(true));
+        assert!(matches_globs(
+            &config.includes,
+            &config.excludes,
+            "src/main.mo"
+        ));
+        assert!(!matches_globs(
+            &config.includes,
+            &config.excludes,
+            "src/generated/bindings.mo"
+        ));
+        assert_eq!(
+            config.overrides["pun-fields"].severity,
+            Some(RuleSeverity::Error)
+        );
+    }
+
+    #[test]
+    fn it_rejects_unknown_fields() {
+        let err =
+            toml::from_str::<ProjectConfig>("format = \"text\"\nseverty = \"error\"").unwrap_err();
+        assert!(err.to_string().contains("unknown field `severty`"), "{err}");
+    }
+
+    #[test]
+    fn nested_configurations_extend_their_parents() {
+        let rules_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("example-rules");
+        let dir = tree(
+            "nested",
+            &[
+                (
+                    "lintoko.toml",
+                    &format!(
+                        r#"
+                        root = true
+                        rules = ["{}/pun-fields.toml", "{}/no-let-else.toml"]
+                        format = "compact"
+                        excludes = ["**/generated/**"]
+
+                        [rule.pun-fields]
+                        excludes = ["test/**"]
+                        "#,
+                        rules_dir.display(),
+                        rules_dir.display()
+                    ),
+                ),
+                (
+                    "backend/lintoko.toml",
+                    &format!(
+                        r#"
+                        rules = ["{}/types-only.toml"]
+
+                        [rule.no-let-else]
+                        enabled = false
+
+                        [rule.pun-fields]
+                        severity = "error"
+                        "#,
+                        rules_dir.display()
+                    ),
+                ),
+                ("backend/types/main.mo", ""),
+                ("test/main.mo", ""),
+            ],
+        );
+        let mut resolver = ConfigResolver::new(None);
+
+        let top = resolver.resolve(&dir.join("test/main.mo")).unwrap();
+        assert_eq!(names(top.rules()), vec!["pun-fields", "no-let-else"]);
+        assert_eq!(
+            names(&top.rules_for(&dir.join("test/main.mo"))),
+            vec!["no-let-else"]
+        );
+        assert!(!top.includes_file(&dir.join("test/generated/main.mo")));
+
+        let backend = resolver
+            .resolve(&dir.join("backend/types/main.mo"))
+            .unwrap();
+        assert_eq!(names(backend.rules()), vec!["pun-fields", "types-only"]);
+        assert_eq!(backend.rules()[0].severity(), RuleSeverity::Error);
+        assert_eq!(backend.format(), Some(OutputFormat::Compact));
+
+        // Directories with the same chain of configuration files share the resolved configuration
+        let other = resolver.resolve_dir(&dir.join("backend")).unwrap();
+        assert!(Arc::ptr_eq(&backend, &other));
+        assert_eq!(
+            names(&resolver.all_rules()),
+            vec!["no-let-else", "pun-fields", "types-only"]
+        );
+        let mut loaded = vec![
+            dir.join("lintoko.toml"),
+            dir.join("backend/lintoko.toml"),
+            rules_dir.join("pun-fields.toml"),
+            rules_dir.join("no-let-else.toml"),
+            rules_dir.join("types-only.toml"),
+        ];
+        loaded.sort();
+        assert_eq!(resolver.loaded_paths(), loaded);
+        assert_eq!(
+            resolver.missing_configs(),
+            vec![
+                dir.join("backend/types/lintoko.toml"),
+                dir.join("test/lintoko.toml")
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn nested_configurations_re_enable_rules() {
+        let rules_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("example-rules");
+        let dir = tree(
+            "re-enable",
+            &[
+                (
+                    "lintoko.toml",
+                    &format!(
+                        r#"
+                        root = true
+                        rules = ["{}/pun-fields.toml"]
+
+                        [rule.pun-fields]
+                        enabled = false
+                        "#,
+                        rules_dir.display()
+                    ),
+                ),
+                (
+                    "strict/lintoko.toml",
+                    r#"
+                    [rule.pun-fields]
+                    enabled = true
+                    "#,
+                ),
+                ("main.mo", ""),
+                ("strict/main.mo", ""),
+            ],
+        );
+        let mut resolver = ConfigResolver::new(None);
+
+        let top = resolver.resolve(&dir.join("main.mo")).unwrap();
+        assert!(top.rules().is_empty());
+        let strict = resolver.resolve(&dir.join("strict/main.mo")).unwrap();
+        assert_eq!(names(strict.rules()), vec!["pun-fields"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn command_line_rules_replace_configured_sources() {
+        let dir = tree(
+            "cli-rules",
+            &[(
+                "lintoko.toml",
+                "root = true\nrules = [\"missing\"]\n[rule.no-such-rule]\nenabled = false\n",
+            )],
+        );
+        let rules = vec![load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap()];
+        let mut resolver = ConfigResolver::new(Some(rules));
+        let resolved = resolver.resolve_dir(&dir).unwrap();
+        assert_eq!(names(resolved.rules()), vec!["pun-fields"]);
+        assert_eq!(resolver.unknown_rules(), vec!["no-such-rule"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn disabled_rules_are_known_to_the_suppression_audit() {
+        let dir = tree("audit-disabled", &[("lintoko.toml", "root = true\n")]);
+        let rules = vec![load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap()];
+        let selection = RuleSelection {
+            disabled: vec!["pun-fields".to_string()],
+            ..RuleSelection::default()
+        };
+        let mut resolver = ConfigResolver::new(Some(rules)).with_selection(selection);
+        let resolved = resolver.resolve_dir(&dir).unwrap();
+        assert!(resolved.rules().is_empty());
+
+        let mut linter = Linter::new(resolved.rules()).with_known_rules(resolver.known_rules());
+        let config = Config {
+            audit_suppressions: true,
+            ..Config::default()
+        };
+        let source =
+            "// lintoko-disable-next-line pun-fields -- generated code\nlet a = { x = x };";
+        let result = linter.lint(&config, "main.mo", source).unwrap();
+        assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use crate::report::{Report, ReportRenderer};
+use crate::{
+    Config, Diagnostic, LintResult, OutputFormat, Rule, github, print_compact_diagnostic,
+    print_pretty_diagnostic, print_text_diagnostic,
+};
+use anyhow::Result;
+use std::io::Write;
+
+/// Turns diagnostics into output. Implement this to plug your own output into a `Reporter`
+pub trait Renderer {
+    /// Called once for every linted file, even when it has no diagnostics
+    fn file(
+        &mut self,
+        path: &str,
+        source: &str,
+        diagnostics: &[Diagnostic],
+        out: &mut dyn Write,
+    ) -> Result<()>;
+
+    /// Called once after all files were linted, e.g. to write a document describing the whole run
+    fn finish(&mut self, _rules: &[Rule], _out: &mut dyn Write) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Graphical output with highlighted source snippets
+pub struct PrettyRenderer;
+
+impl Renderer for PrettyRenderer {
+    fn file(
+        &mut self,
+        path: &str,
+        source: &str,
+        diagnostics: &[Diagnostic],
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        for d in diagnostics {
+            writeln!(out, "{}", print_pretty_diagnostic(path, source, d))?;
+        }
+        Ok(())
+    }
+}
+
+/// Plain text output, easier to consume for screen readers or AI tools
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    fn file(
+        &mut self,
+        path: &str,
+        source: &str,
+        diagnostics: &[Diagnostic],
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        for d in diagnostics {
+            writeln!(out, "{}", print_text_diagnostic(path, source, d))?;
+        }
+        Ok(())
+    }
+}
+
+/// One line per diagnostic: `path:line:col: severity: message [rule]`
+pub struct CompactRenderer {
+    pub end_positions: bool,
+}
+
+impl Renderer for CompactRenderer {
+    fn file(
+        &mut self,
+        path: &str,
+        _source: &str,
+        diagnostics: &[Diagnostic],
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        for d in diagnostics {
+            writeln!(
+                out,
+                "{}",
+                print_compact_diagnostic(path, d, self.end_positions)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// GitHub Actions workflow commands
+pub struct GithubRenderer;
+
+impl Renderer for GithubRenderer {
+    fn file(
+        &mut self,
+        path: &str,
+        source: &str,
+        diagnostics: &[Diagnostic],
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        for d in diagnostics {
+            writeln!(out, "{}", github::print_diagnostic(path, source, d))?;
+        }
+        Ok(())
+    }
+}
+
+/// The built-in renderer for `config.format`
+pub fn renderer(config: &Config) -> Box<dyn Renderer> {
+    match config.format {
+        OutputFormat::Pretty => Box::new(PrettyRenderer),
+        OutputFormat::Text => Box::new(TextRenderer),
+        OutputFormat::Compact => Box::new(CompactRenderer {
+            end_positions: config.end_positions,
+        }),
+        OutputFormat::Github => Box::new(GithubRenderer),
+        OutputFormat::Json
+        | OutputFormat::Sarif
+        | OutputFormat::Checkstyle
+        | OutputFormat::Junit
+        | OutputFormat::Gitlab
+        | OutputFormat::Html
+        | OutputFormat::Markdown => Box::new(ReportRenderer {
+            config: config.clone(),
+            report: Report::default(),
+        }),
+    }
+}
+
+/// Drives a `Renderer` over the results of a run. Several reporters can be fed from the same
+/// `LintResult`s, so a run can produce multiple outputs without linting twice.
+pub struct Reporter<W: Write> {
+    renderer: Box<dyn Renderer>,
+    out: W,
+}
+
+impl<W: Write> Reporter<W> {
+    /// Renders in `config.format`, with all other rendering options taken from `config` as well
+    pub fn new(config: Config, out: W) -> Self {
+        Self::with_renderer(renderer(&config), out)
+    }
+
+    pub fn with_renderer(renderer: Box<dyn Renderer>, out: W) -> Self {
+        Self { renderer, out }
+    }
+
+    pub fn add(&mut self, path: &str, source: &str, result: &LintResult) -> Result<()> {
+        self.renderer
+            .file(path, source, &result.diagnostics, &mut self.out)
+    }
+
+    /// Lets the renderer write out anything it collected, and flushes the output
+    pub fn finish(mut self, rules: &[Rule]) -> Result<()> {
+        self.renderer.finish(rules, &mut self.out)?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{lint, load_rule_from_file};
+    use std::path::Path;
+
+    #[test]
+    fn reporters_share_one_lint_result() {
+        let rules = [load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap()];
+        let config = Config::default();
+        let res = lint(&config, "main.mo", "{ x = x }", &rules).unwrap();
+
+        let mut compact: Vec<u8> = vec![];
+        let mut json: Vec<u8> = vec![];
+        let mut compact_reporter = Reporter::new(
+            Config {
+                format: OutputFormat::Compact,
+                ..config.clone()
+            },
+            &mut compact,
+        );
+        let mut json_reporter = Reporter::new(
+            Config {
+                format: OutputFormat::Json,
+                ..config.clone()
+            },
+            &mut json,
+        );
+        compact_reporter.add("main.mo", "{ x = x }", &res).unwrap();
+        json_reporter.add("main.mo", "{ x = x }", &res).unwrap();
+        compact_reporter.finish(&rules).unwrap();
+        json_reporter.finish(&rules).unwrap();
+
+        assert!(
+            str::from_utf8(&compact)
+                .unwrap()
+                .starts_with("main.mo:1:3: warning: ")
+        );
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["diagnostics"][0]["rule"], "pun-fields");
+    }
+
+    struct RuleCounter {
+        count: usize,
+    }
+
+    impl Renderer for RuleCounter {
+        fn file(
+            &mut self,
+            _path: &str,
+            _source: &str,
+            diagnostics: &[Diagnostic],
+            _out: &mut dyn Write,
+        ) -> Result<()> {
+            self.count += diagnostics.len();
+            Ok(())
+        }
+
+        fn finish(&mut self, rules: &[Rule], out: &mut dyn Write) -> Result<()> {
+            writeln!(out, "{} findings from {} rules", self.count, rules.len())?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn custom_renderers_plug_into_reporters() {
+        let rules = [load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap()];
+        let res = lint(&Config::default(), "main.mo", "{ x = x; y = y }", &rules).unwrap();
+        let mut out: Vec<u8> = vec![];
+        let mut reporter = Reporter::with_renderer(Box::new(RuleCounter { count: 0 }), &mut out);
+        reporter.add("main.mo", "{ x = x; y = y }", &res).unwrap();
+        reporter.finish(&rules).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "2 findings from 1 rules\n");
+    }
+}
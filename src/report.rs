@@ -0,0 +1,120 @@
+use crate::{
+    Config, Diagnostic, OutputFormat, Renderer, Rule, RuleSeverity, gitlab, html, json, markdown,
+    sarif, xml,
+};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+pub(crate) struct FileReport {
+    pub(crate) path: String,
+    pub(crate) source: String,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+/// Collects the results of all linted files, for output formats that describe the whole run in a single document
+#[derive(Default)]
+pub(crate) struct Report {
+    pub(crate) files: Vec<FileReport>,
+    pub(crate) error_count: usize,
+    pub(crate) warning_count: usize,
+    pub(crate) info_count: usize,
+    pub(crate) hint_count: usize,
+}
+
+impl Report {
+    pub(crate) fn add(&mut self, path: &str, source: &str, diagnostics: &[Diagnostic]) {
+        for d in diagnostics {
+            match d.severity {
+                RuleSeverity::Error => self.error_count += 1,
+                RuleSeverity::Warning => self.warning_count += 1,
+                RuleSeverity::Info => self.info_count += 1,
+                RuleSeverity::Hint => self.hint_count += 1,
+                RuleSeverity::Off => {}
+            }
+        }
+        self.files.push(FileReport {
+            path: path.to_string(),
+            source: source.to_string(),
+            diagnostics: diagnostics.to_vec(),
+        });
+    }
+
+    /// How many diagnostics each rule reported, per severity
+    pub(crate) fn per_rule(&self) -> BTreeMap<(&str, RuleSeverity), usize> {
+        let mut per_rule = BTreeMap::new();
+        for d in self.files.iter().flat_map(|f| &f.diagnostics) {
+            *per_rule.entry((d.rule.as_str(), d.severity)).or_default() += 1;
+        }
+        per_rule
+    }
+
+    /// Writes the report in `config.format`. Does nothing for formats that are printed per file
+    pub(crate) fn write(&self, config: &Config, rules: &[Rule], out: impl Write) -> Result<()> {
+        match config.format {
+            OutputFormat::Pretty
+            | OutputFormat::Text
+            | OutputFormat::Compact
+            | OutputFormat::Github => Ok(()),
+            OutputFormat::Json => json::write(self, out),
+            OutputFormat::Sarif => sarif::write(self, config, rules, out),
+            OutputFormat::Checkstyle => xml::write_checkstyle(self, out),
+            OutputFormat::Junit => xml::write_junit(self, out),
+            OutputFormat::Gitlab => gitlab::write(self, out),
+            OutputFormat::Html => html::write(self, out),
+            OutputFormat::Markdown => markdown::write(self, config, out),
+        }
+    }
+}
+
+/// Renders the aggregated formats, which can only be written once all files are linted
+pub(crate) struct ReportRenderer {
+    pub(crate) config: Config,
+    pub(crate) report: Report,
+}
+
+impl Renderer for ReportRenderer {
+    fn file(
+        &mut self,
+        path: &str,
+        source: &str,
+        diagnostics: &[Diagnostic],
+        _out: &mut dyn Write,
+    ) -> Result<()> {
+        self.report.add(path, source, diagnostics);
+        Ok(())
+    }
+
+    fn finish(&mut self, rules: &[Rule], out: &mut dyn Write) -> Result<()> {
+        self.report.write(&self.config, rules, out)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::{Config, Reporter, Rule, lint_file, load_rule_from_file};
+    use std::path::Path;
+
+    pub(crate) fn pun_fields() -> [Rule; 1] {
+        [load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap()]
+    }
+
+    /// Lints `files` and renders them through a `Reporter`, the same way the CLI does
+    pub(crate) fn render(config: &Config, rules: &[Rule], files: &[(&str, &str)]) -> Vec<u8> {
+        assert!(config.format.is_aggregated());
+        let mut out: Vec<u8> = vec![];
+        let mut reporter = Reporter::new(config.clone(), &mut out);
+        for (path, source) in files {
+            let mut stdout: Vec<u8> = vec![];
+            let res = lint_file(config, path, source, rules, &mut stdout).unwrap();
+            assert!(
+                stdout.is_empty(),
+                "{:?} output is only written by the report",
+                config.format
+            );
+            reporter.add(path, source, &res).unwrap();
+        }
+        reporter.finish(rules).unwrap();
+        out
+    }
+}
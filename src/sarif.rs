@@ -0,0 +1,163 @@
+use crate::report::Report;
+use crate::{Config, Diagnostic, Rule, RuleSeverity, char_column};
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::io::Write;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn level(severity: RuleSeverity) -> &'static str {
+    match severity {
+        RuleSeverity::Off => "none",
+        RuleSeverity::Hint | RuleSeverity::Info => "note",
+        RuleSeverity::Warning => "warning",
+        RuleSeverity::Error => "error",
+    }
+}
+
+// SARIF consumers expect forward slashes in relative artifact URIs
+fn artifact_uri(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+// We declare `unicodeCodePoints` as the column kind for the run
+fn region(source: &str, diagnostic: &Diagnostic) -> Value {
+    let range = diagnostic.range;
+    json!({
+        "startLine": range.start_point.row + 1,
+        "startColumn": char_column(source, range.start_byte, range.start_point),
+        "endLine": range.end_point.row + 1,
+        "endColumn": char_column(source, range.end_byte, range.end_point),
+        "byteOffset": range.start_byte,
+        "byteLength": range.end_byte - range.start_byte,
+    })
+}
+
+fn reporting_descriptor(config: &Config, rule: &Rule) -> Value {
+    json!({
+        "id": rule.name,
+        "shortDescription": { "text": rule.description },
+        "defaultConfiguration": {
+            "level": level(
+                config
+                    .severity_override
+                    .unwrap_or(rule.severity_on(config.today()))
+            ),
+        },
+    })
+}
+
+fn result(rule_index: Option<usize>, path: &str, source: &str, diagnostic: &Diagnostic) -> Value {
+    let artifact_location = json!({ "uri": artifact_uri(path) });
+    let region = region(source, diagnostic);
+    let mut result = json!({
+        "ruleId": diagnostic.rule,
+        "level": level(diagnostic.severity),
+        "message": { "text": diagnostic.description },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": artifact_location,
+                "region": region,
+            },
+        }],
+    });
+    if let Some(index) = rule_index {
+        result["ruleIndex"] = json!(index);
+    }
+    if let Some(fix) = &diagnostic.fix {
+        result["fixes"] = json!([{
+            "description": { "text": format!("Apply the fix for '{}'", diagnostic.rule) },
+            "artifactChanges": [{
+                "artifactLocation": artifact_location,
+                "replacements": [{
+                    "deletedRegion": region,
+                    "insertedContent": { "text": fix },
+                }],
+            }],
+        }]);
+    }
+    result
+}
+
+/// Writes the report as a SARIF 2.1.0 log with a single run
+pub(crate) fn write(
+    report: &Report,
+    config: &Config,
+    rules: &[Rule],
+    mut out: impl Write,
+) -> Result<()> {
+    let results: Vec<Value> = report
+        .files
+        .iter()
+        .flat_map(|file| {
+            file.diagnostics.iter().map(|d| {
+                let rule_index = rules.iter().position(|r| r.name == d.rule);
+                result(rule_index, &file.path, &file.source, d)
+            })
+        })
+        .collect();
+    let log = json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules.iter().map(|r| reporting_descriptor(config, r)).collect::<Vec<_>>(),
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+    serde_json::to_writer_pretty(&mut out, &log)?;
+    writeln!(&mut out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::report::test::render;
+    use crate::{OutputFormat, load_rule_from_file};
+    use std::path::Path;
+
+    #[test]
+    fn it_writes_rules_results_and_fixes() {
+        let config = Config {
+            format: OutputFormat::Sarif,
+            ..Config::default()
+        };
+        let rules = [
+            load_rule_from_file(Path::new("example-rules/no-let-else.toml")).unwrap(),
+            load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap(),
+        ];
+        let sarif = render(&config, &rules, &[("src\\main.mo", "/* é */ { x = x }")]);
+
+        let log: Value = serde_json::from_slice(&sarif).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        let descriptors = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert_eq!(descriptors.len(), 2);
+        assert_eq!(descriptors[0]["id"], "no-let-else");
+        assert_eq!(descriptors[0]["defaultConfiguration"]["level"], "error");
+        assert_eq!(descriptors[1]["id"], "pun-fields");
+        assert_eq!(descriptors[1]["defaultConfiguration"]["level"], "warning");
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert_eq!(result["ruleId"], "pun-fields");
+        assert_eq!(result["ruleIndex"], 1);
+        assert_eq!(result["level"], "warning");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/main.mo");
+        // `é` takes up two bytes, but is a single code point
+        assert_eq!(location["region"]["startColumn"], 11);
+        assert_eq!(location["region"]["byteOffset"], 11);
+        let replacement = &result["fixes"][0]["artifactChanges"][0]["replacements"][0];
+        assert_eq!(replacement["insertedContent"]["text"], "x");
+    }
+}
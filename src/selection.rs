@@ -0,0 +1,136 @@
+use crate::{Rule, RuleSeverity};
+use anyhow::{Result, bail};
+
+/// Per-rule settings from the command line. They are applied on top of all configuration files
+#[derive(Debug, Clone, Default)]
+pub struct RuleSelection {
+    pub severities: Vec<(String, RuleSeverity)>,
+    pub disabled: Vec<String>,
+    /// When not empty, all other rules are disabled
+    pub only: Vec<String>,
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The known name closest to `name`, if it is close enough to be a likely typo
+pub fn did_you_mean<'a>(name: &str, known: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(2);
+    known
+        .into_iter()
+        .map(|k| (edit_distance(name, k), k))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, k)| k)
+}
+
+impl RuleSelection {
+    /// Fails on names that don't match any of the `known` rules, suggesting the closest match
+    pub fn validate(&self, known: &[&str]) -> Result<()> {
+        let names = self
+            .severities
+            .iter()
+            .map(|(name, _)| ("--rule-severity", name))
+            .chain(self.disabled.iter().map(|name| ("--disable", name)))
+            .chain(self.only.iter().map(|name| ("--only", name)));
+        for (flag, name) in names {
+            if known.contains(&name.as_str()) {
+                continue;
+            }
+            match did_you_mean(name, known.iter().copied()) {
+                Some(suggestion) => {
+                    bail!("Unknown rule '{name}' passed to `{flag}`. Did you mean '{suggestion}'?")
+                }
+                None => bail!("Unknown rule '{name}' passed to `{flag}`"),
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply(&self, rules: &mut Vec<Rule>) {
+        rules.retain(|r| {
+            (self.only.is_empty() || self.only.contains(&r.name))
+                && !self.disabled.contains(&r.name)
+        });
+        for rule in rules {
+            // The last occurrence wins, like for any other repeated flag
+            if let Some((_, severity)) = self.severities.iter().rev().find(|(n, _)| *n == rule.name)
+            {
+                rule.pin_severity(*severity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::load_rule_from_file;
+    use std::path::Path;
+
+    fn example_rules() -> Vec<Rule> {
+        ["pun-fields", "no-let-else", "no-result"]
+            .iter()
+            .map(|name| {
+                load_rule_from_file(Path::new(&format!("example-rules/{name}.toml"))).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_selects_and_overrides_rules() {
+        let selection = RuleSelection {
+            severities: vec![("pun-fields".to_string(), RuleSeverity::Error)],
+            disabled: vec!["no-result".to_string()],
+            only: vec!["pun-fields".to_string(), "no-result".to_string()],
+        };
+        let mut rules = example_rules();
+        selection.apply(&mut rules);
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].name(), "pun-fields");
+        assert_eq!(rules[0].severity(), RuleSeverity::Error);
+    }
+
+    #[test]
+    fn unknown_rules_suggest_the_closest_name() {
+        let known = ["pun-fields", "no-let-else", "no-result"];
+        let selection = RuleSelection {
+            disabled: vec!["no-results".to_string()],
+            ..RuleSelection::default()
+        };
+        let err = selection.validate(&known).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown rule 'no-results' passed to `--disable`. Did you mean 'no-result'?"
+        );
+
+        let selection = RuleSelection {
+            only: vec!["nesting-limit".to_string()],
+            ..RuleSelection::default()
+        };
+        let err = selection.validate(&known).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown rule 'nesting-limit' passed to `--only`"
+        );
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("pun-fields", "pun-fields"), 0);
+        assert_eq!(edit_distance("pun-field", "pun-fields"), 1);
+        assert_eq!(edit_distance("no-let-esle", "no-let-else"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
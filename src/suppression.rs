@@ -0,0 +1,510 @@
+use crate::{Diagnostic, RuleSeverity};
+use jiff::civil::Date;
+use serde::Serialize;
+use std::collections::HashMap;
+use tree_sitter::{Node, Range};
+
+const DISABLE_NEXT_LINE: &str = "lintoko-disable-next-line";
+const DISABLE: &str = "lintoko-disable";
+const ENABLE: &str = "lintoko-enable";
+const DISABLE_FILE: &str = "lintoko-disable-file";
+
+const INVALID_SUPPRESSION: &str = "invalid-suppression";
+const EXPIRED_SUPPRESSION: &str = "expired-suppression";
+/// The rules `check` reports under, on every run
+pub(crate) const CHECK_RULES: [&str; 2] = [INVALID_SUPPRESSION, EXPIRED_SUPPRESSION];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SuppressionKind {
+    NextLine,
+    Region,
+    File,
+}
+
+/// A `// lintoko-disable...` comment in the source
+#[derive(Debug, Clone)]
+pub struct Suppression {
+    pub kind: SuppressionKind,
+    /// The rules the comment names. Empty means all rules
+    pub rules: Vec<String>,
+    /// The text after `--`
+    pub reason: Option<String>,
+    /// The suppression stops applying after this date
+    pub until: Option<Date>,
+    /// Why the comment couldn't be fully understood, e.g. an invalid `until` date
+    pub invalid: Option<String>,
+    /// The comment itself
+    pub range: Range,
+    /// 0-based rows the suppression covers. Diagnostics have to lie entirely within them, except
+    /// for `NextLine`, which only looks at the row a diagnostic starts on
+    rows: std::ops::Range<usize>,
+    /// Rows at which single rules of a region were re-enabled
+    ends: HashMap<String, usize>,
+}
+
+impl Suppression {
+    fn names(&self, rule: &str) -> bool {
+        self.rules.is_empty() || self.rules.iter().any(|r| r == rule)
+    }
+
+    pub fn is_expired(&self, today: Date) -> bool {
+        self.until.is_some_and(|until| today > until)
+    }
+
+    /// Invalid and expired suppressions don't suppress anything
+    fn applies(&self, today: Date) -> bool {
+        self.invalid.is_none() && !self.is_expired(today)
+    }
+
+    fn suppresses(&self, diagnostic: &Diagnostic) -> bool {
+        let Range {
+            start_point,
+            end_point,
+            ..
+        } = diagnostic.range;
+        if !self.names(&diagnostic.rule) {
+            return false;
+        }
+        if self.kind == SuppressionKind::NextLine {
+            return start_point.row == self.rows.start;
+        }
+        // A range that ends right after a newline doesn't cover the next row
+        let last_row = if end_point.column == 0 && end_point.row > start_point.row {
+            end_point.row - 1
+        } else {
+            end_point.row
+        };
+        let end = self
+            .ends
+            .get(&diagnostic.rule)
+            .copied()
+            .unwrap_or(self.rows.end);
+        self.rows.start <= start_point.row && last_row < end
+    }
+}
+
+struct Directive<'a> {
+    name: &'a str,
+    rules: Vec<String>,
+    reason: Option<String>,
+    until: Option<&'a str>,
+}
+
+/// Parses `// <directive> [rule, ...] [until YYYY-MM-DD] [-- reason]`
+fn parse_directive(comment: &str) -> Option<Directive<'_>> {
+    let text = comment.strip_prefix("//")?.trim();
+    let (name, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    if ![DISABLE_NEXT_LINE, DISABLE, ENABLE, DISABLE_FILE].contains(&name) {
+        return None;
+    }
+    let rest = rest.trim();
+    let (rest, reason) = match rest.strip_prefix("--") {
+        Some(reason) => ("", Some(reason)),
+        None => match rest.split_once(" --") {
+            Some((rest, reason)) => (rest, Some(reason)),
+            None => (rest, None),
+        },
+    };
+    let reason = reason.map(str::trim).filter(|r| !r.is_empty());
+    let (rest, until) = match rest.strip_prefix("until ") {
+        Some(date) => ("", Some(date.trim())),
+        None => match rest.rsplit_once(" until ") {
+            Some((rest, date)) => (rest, Some(date.trim())),
+            None => (rest, None),
+        },
+    };
+    let rules = rest
+        .split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(str::to_string)
+        .collect();
+    Some(Directive {
+        name,
+        rules,
+        reason: reason.map(str::to_string),
+        until,
+    })
+}
+
+// Comments are extras, so they can show up anywhere in the tree
+fn comments<'a>(root: Node<'a>) -> Vec<Node<'a>> {
+    let mut comments = vec![];
+    let mut cursor = root.walk();
+    'walk: loop {
+        if cursor.node().kind().contains("comment") {
+            comments.push(cursor.node());
+        }
+        if cursor.goto_first_child() || cursor.goto_next_sibling() {
+            continue;
+        }
+        loop {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+            if cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+    comments
+}
+
+/// Collects all suppression comments in the file. A `lintoko-disable` without a matching
+/// `lintoko-enable` lasts until the end of the file.
+pub(crate) fn collect(root: Node, input: &str) -> Vec<Suppression> {
+    let mut suppressions: Vec<Suppression> = vec![];
+    // Indices into `suppressions` of regions that haven't been closed yet
+    let mut open: Vec<usize> = vec![];
+    for comment in comments(root) {
+        let Ok(text) = comment.utf8_text(input.as_bytes()) else {
+            continue;
+        };
+        let Some(directive) = parse_directive(text) else {
+            continue;
+        };
+        let row = comment.start_position().row;
+        if directive.name == ENABLE {
+            open.retain(|&i| {
+                let region = &mut suppressions[i];
+                if directive.rules.is_empty() {
+                    region.rows.end = row;
+                    return false;
+                }
+                // A region for all rules can only be closed by a bare `lintoko-enable`
+                if region.rules.is_empty() {
+                    return true;
+                }
+                for rule in &directive.rules {
+                    if region.rules.contains(rule) {
+                        region.ends.entry(rule.clone()).or_insert(row);
+                    }
+                }
+                region.ends.len() < region.rules.len()
+            });
+            continue;
+        }
+        let (kind, rows) = match directive.name {
+            DISABLE_NEXT_LINE => (SuppressionKind::NextLine, row + 1..row + 2),
+            DISABLE => (SuppressionKind::Region, row..usize::MAX),
+            DISABLE_FILE => (SuppressionKind::File, 0..usize::MAX),
+            _ => unreachable!(),
+        };
+        let (until, invalid) = match directive.until.map(str::parse::<Date>) {
+            None => (None, None),
+            Some(Ok(date)) => (Some(date), None),
+            Some(Err(_)) => (
+                None,
+                Some(format!(
+                    "Invalid date '{}', expected `until YYYY-MM-DD`",
+                    directive.until.unwrap_or_default()
+                )),
+            ),
+        };
+        if kind == SuppressionKind::Region {
+            open.push(suppressions.len());
+        }
+        suppressions.push(Suppression {
+            kind,
+            rules: directive.rules,
+            reason: directive.reason,
+            until,
+            invalid,
+            range: comment.range(),
+            rows,
+            ends: HashMap::new(),
+        });
+    }
+    suppressions
+}
+
+/// Removes suppressed diagnostics. Returns the rules each suppression was used for, with `None`
+/// standing in for all rules
+pub(crate) fn apply(
+    suppressions: &[Suppression],
+    today: Date,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<Vec<Option<String>>> {
+    let mut used = vec![vec![]; suppressions.len()];
+    diagnostics.retain(|d| {
+        let mut suppressed = false;
+        for (i, s) in suppressions.iter().enumerate() {
+            if !s.applies(today) || !s.suppresses(d) {
+                continue;
+            }
+            suppressed = true;
+            let rule = (!s.rules.is_empty()).then(|| d.rule.clone());
+            if !used[i].contains(&rule) {
+                used[i].push(rule);
+            }
+        }
+        !suppressed
+    });
+    used
+}
+
+fn audit_diagnostic(
+    suppression: &Suppression,
+    rule: &str,
+    description: String,
+    severity: RuleSeverity,
+) -> Diagnostic {
+    Diagnostic {
+        rule: rule.to_string(),
+        description,
+        range: suppression.range,
+        fix: None,
+        severity,
+        context: "comment".to_string(),
+        escalation: None,
+    }
+}
+
+/// Reports suppressions that can't be understood or have expired
+pub(crate) fn check(suppressions: &[Suppression], today: Date) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for s in suppressions {
+        if let Some(message) = &s.invalid {
+            diagnostics.push(audit_diagnostic(
+                s,
+                INVALID_SUPPRESSION,
+                message.clone(),
+                RuleSeverity::Error,
+            ));
+        }
+        if let Some(until) = s.until
+            && s.is_expired(today)
+        {
+            diagnostics.push(audit_diagnostic(
+                s,
+                EXPIRED_SUPPRESSION,
+                format!("Suppression expired on {until}"),
+                RuleSeverity::Error,
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Reports suppressions that don't suppress anything, name unknown rules, or are missing a
+/// reason. Only suppressions for the rules that `ran` on the file can be unused
+pub(crate) fn audit(
+    suppressions: &[Suppression],
+    used: &[Vec<Option<String>>],
+    known_rules: &[String],
+    ran: &[&str],
+    today: Date,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for (s, used) in suppressions.iter().zip(used) {
+        // `check` already reports these, and they can't be used anyway
+        if !s.applies(today) {
+            continue;
+        }
+        if s.reason.is_none() {
+            diagnostics.push(audit_diagnostic(
+                s,
+                "suppression-without-reason",
+                "Suppression is missing a reason. Add one after `--`".to_string(),
+                RuleSeverity::Warning,
+            ));
+        }
+        if s.rules.is_empty() {
+            if used.is_empty() {
+                diagnostics.push(audit_diagnostic(
+                    s,
+                    "unused-suppression",
+                    "Suppression doesn't suppress anything".to_string(),
+                    RuleSeverity::Warning,
+                ));
+            }
+            continue;
+        }
+        for rule in &s.rules {
+            if !known_rules.contains(rule) {
+                diagnostics.push(audit_diagnostic(
+                    s,
+                    "unknown-suppression-rule",
+                    format!("Suppression names unknown rule '{rule}'"),
+                    RuleSeverity::Warning,
+                ));
+            } else if ran.contains(&rule.as_str()) && !used.contains(&Some(rule.clone())) {
+                diagnostics.push(audit_diagnostic(
+                    s,
+                    "unused-suppression",
+                    format!("Suppression for '{rule}' doesn't suppress anything"),
+                    RuleSeverity::Warning,
+                ));
+            }
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Config, lint, load_rule_from_file};
+    use jiff::civil::date;
+    use std::path::Path;
+
+    fn lint_with(config: &Config, source: &str) -> Vec<(String, usize)> {
+        let rules = [load_rule_from_file(Path::new("example-rules/pun-fields.toml")).unwrap()];
+        let res = lint(config, "main.mo", source, &rules).unwrap();
+        res.diagnostics
+            .iter()
+            .map(|d| (d.rule.clone(), d.range.start_point.row))
+            .collect()
+    }
+
+    fn lint_rows(source: &str) -> Vec<usize> {
+        lint_with(&Config::default(), source)
+            .into_iter()
+            .map(|(_, row)| row)
+            .collect()
+    }
+
+    fn audit_config() -> Config {
+        Config {
+            audit_suppressions: true,
+            today: Some(date(2026, 6, 1)),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn parses_directives() {
+        let d = parse_directive("// lintoko-disable-next-line pun-fields, no-let-else").unwrap();
+        assert_eq!(d.name, DISABLE_NEXT_LINE);
+        assert_eq!(d.rules, vec!["pun-fields", "no-let-else"]);
+        assert_eq!(d.reason, None);
+
+        let d = parse_directive("//lintoko-enable").unwrap();
+        assert_eq!(d.name, ENABLE);
+        assert!(d.rules.is_empty());
+
+        let d = parse_directive("// lintoko-disable pun-fields until 2027-01-01 -- legacy API")
+            .unwrap();
+        assert_eq!(d.rules, vec!["pun-fields"]);
+        assert_eq!(d.until, Some("2027-01-01"));
+        assert_eq!(d.reason.as_deref(), Some("legacy API"));
+
+        let d = parse_directive("// lintoko-disable-file -- generated").unwrap();
+        assert!(d.rules.is_empty());
+        assert_eq!(d.reason.as_deref(), Some("generated"));
+
+        assert!(parse_directive("// lintoko-disabled pun-fields").is_none());
+        assert!(parse_directive("/* lintoko-disable pun-fields */").is_none());
+    }
+
+    #[test]
+    fn disable_next_line_only_covers_the_next_line() {
+        let source = "// lintoko-disable-next-line pun-fields\n\
+                      let a = { x = x };\n\
+                      let b = { y = y };";
+        assert_eq!(lint_rows(source), vec![2]);
+    }
+
+    #[test]
+    fn other_rules_are_not_suppressed() {
+        let source = "// lintoko-disable-next-line no-let-else\n\
+                      let a = { x = x };";
+        assert_eq!(lint_rows(source), vec![1]);
+    }
+
+    #[test]
+    fn regions_end_at_enable() {
+        let source = "let a = { x = x };\n\
+                      // lintoko-disable pun-fields\n\
+                      let b = { y = y };\n\
+                      // lintoko-enable pun-fields\n\
+                      let c = { z = z };";
+        assert_eq!(lint_rows(source), vec![0, 4]);
+    }
+
+    #[test]
+    fn regions_only_cover_findings_that_lie_within_them() {
+        let source = "// lintoko-disable pun-fields\n\
+                      let a = { x =\n\
+                      // lintoko-enable pun-fields\n\
+                      x };\n\
+                      // lintoko-disable-next-line pun-fields\n\
+                      let b = { y =\n\
+                      y };\n\
+                      // lintoko-disable pun-fields\n\
+                      let c = { z =\n\
+                      z };";
+        // The first finding starts in the region but ends after it
+        assert_eq!(lint_rows(source), vec![1]);
+    }
+
+    #[test]
+    fn enabling_one_rule_keeps_the_others_disabled() {
+        let source = "// lintoko-disable pun-fields, no-let-else\n\
+                      // lintoko-enable no-let-else\n\
+                      let a = { x = x };";
+        assert_eq!(lint_rows(source), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn disable_file_covers_everything() {
+        let source = "let a = { x = x };\n\
+                      // lintoko-disable-file pun-fields\n\
+                      let b = { y = y };";
+        assert_eq!(lint_rows(source), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn audit_reports_unused_unknown_and_reasonless_suppressions() {
+        let source = "// lintoko-disable-next-line pun-fields -- keeps the diff small\n\
+                      let a = { x = x };\n\
+                      // lintoko-disable-next-line pun-fields, no-such-rule\n\
+                      let b = 1;";
+        assert_eq!(
+            lint_with(&audit_config(), source),
+            vec![
+                ("suppression-without-reason".to_string(), 2),
+                ("unused-suppression".to_string(), 2),
+                ("unknown-suppression-rule".to_string(), 2),
+            ]
+        );
+        // Without the audit, suppressions are applied silently
+        assert!(lint_with(&Config::default(), source).is_empty());
+    }
+
+    #[test]
+    fn expired_suppressions_stop_applying() {
+        let source = "// lintoko-disable-next-line pun-fields until 2026-05-31 -- migration\n\
+                      let a = { x = x };";
+        let expired = vec![
+            ("expired-suppression".to_string(), 0),
+            ("pun-fields".to_string(), 1),
+        ];
+        assert_eq!(lint_with(&audit_config(), source), expired);
+        // Expiry is reported without the audit, too
+        let without_audit = Config {
+            audit_suppressions: false,
+            ..audit_config()
+        };
+        assert_eq!(lint_with(&without_audit, source), expired);
+        let not_expired = Config {
+            today: Some(date(2026, 5, 31)),
+            ..audit_config()
+        };
+        assert!(lint_with(&not_expired, source).is_empty());
+    }
+
+    #[test]
+    fn invalid_dates_never_suppress() {
+        let source = "// lintoko-disable-next-line pun-fields until someday -- migration\n\
+                      let a = { x = x };";
+        assert_eq!(
+            lint_with(&Config::default(), source),
+            vec![
+                ("invalid-suppression".to_string(), 0),
+                ("pun-fields".to_string(), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! `--watch`: lints all files once, then re-lints the files that change until interrupted
+
+use crate::{Args, ConfigError, Linters, Setup, expand_inputs, report_tool_diagnostics};
+use anyhow::{Context, Result, anyhow};
+use lintoko::ResolvedConfig;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::debug;
+
+/// There's no portable way to get notified about file changes without extra dependencies, so files are polled
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// How often the inputs are expanded again to find new files. Known files are checked on every poll
+const GLOB_INTERVAL: Duration = Duration::from_secs(3);
+
+/// The modification time and size of a file, to notice changes without reading it
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Stamps of the rule and configuration files, including every rule in a rule directory. Adding or removing a
+/// rule changes them as well
+fn rule_stamps(paths: &[PathBuf]) -> BTreeMap<PathBuf, Stamp> {
+    let mut stamps = BTreeMap::new();
+    for path in paths {
+        match fs::read_dir(path) {
+            Ok(entries) => {
+                for entry in entries.filter_map(Result::ok) {
+                    let path = entry.path();
+                    if path.extension().unwrap_or_default() == "toml" {
+                        stamps.insert(path.clone(), stamp(&path));
+                    }
+                }
+            }
+            Err(_) => {
+                stamps.insert(path.clone(), stamp(path));
+            }
+        }
+    }
+    stamps
+}
+
+/// What to lint again after the rules changed, even though the file itself didn't
+enum Stale {
+    Nothing,
+    /// Only the named rules changed, the findings of all others are kept
+    Rules(Vec<String>),
+    Everything,
+}
+
+struct WatchedFile {
+    stamp: Stamp,
+    stale: Stale,
+    /// The previous version of the file, to parse the next one incrementally
+    parsed: Option<lintoko::ParsedFile>,
+    /// `None` if the configuration excludes the file
+    result: Option<lintoko::LintResult>,
+}
+
+struct Watcher<'a> {
+    args: &'a Args,
+    config: lintoko::Config,
+    setup: Setup,
+    linters: Linters,
+    rule_stamps: BTreeMap<PathBuf, Stamp>,
+    /// Why the rules couldn't be reloaded. The previous rules stay in use until this is fixed
+    load_error: Option<String>,
+    /// The expanded inputs, and when they were expanded. `None` expands them on the next poll
+    inputs: Option<(BTreeSet<PathBuf>, Instant)>,
+    files: BTreeMap<PathBuf, WatchedFile>,
+}
+
+/// The names of the rules that changed between `old` and `new` for the file at the absolute path `file`,
+/// including added and removed ones
+fn changed_rules(old: &ResolvedConfig, new: &ResolvedConfig, file: &Path) -> Stale {
+    if old.includes_file(file) != new.includes_file(file) {
+        return Stale::Everything;
+    }
+    let fingerprints = |config: &ResolvedConfig| -> BTreeSet<(String, String)> {
+        let rules = config.rules_for(file);
+        rules
+            .iter()
+            .map(|r| (r.name().to_string(), r.fingerprint()))
+            .collect()
+    };
+    let (old, new) = (fingerprints(old), fingerprints(new));
+    let mut changed: Vec<String> = old
+        .symmetric_difference(&new)
+        .map(|(name, _)| name.clone())
+        .collect();
+    changed.dedup();
+    if changed.is_empty() {
+        Stale::Nothing
+    } else {
+        Stale::Rules(changed)
+    }
+}
+
+impl Watcher<'_> {
+    /// The rule sources and configuration files to watch, including configuration files that don't exist yet
+    fn rule_paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.args.rules.clone();
+        paths.extend(self.setup.resolver.loaded_paths());
+        paths.extend(self.setup.resolver.missing_configs());
+        paths
+    }
+
+    fn load(args: &Args) -> Result<Setup> {
+        let mut setup = Setup::load(args)?;
+        let files = expand_inputs(&setup.inputs).unwrap_or_default();
+        setup.resolve_all(&files)?;
+        Ok(setup)
+    }
+
+    /// Reloads the rules and configuration files if any of them changed. Returns whether they did
+    fn reload_rules(&mut self) -> bool {
+        // Directories that were looked up since the last reload, e.g. for new files, had no configuration file
+        // then. Creating one later reloads the rules
+        for path in self.setup.resolver.missing_configs() {
+            self.rule_stamps.entry(path).or_insert(None);
+        }
+        let stamps = rule_stamps(&self.rule_paths());
+        if stamps == self.rule_stamps {
+            return false;
+        }
+        debug!("Reloading the rules");
+        match Self::load(self.args) {
+            Ok(setup) => {
+                let old_linters =
+                    std::mem::replace(&mut self.linters, Linters::new(&setup.resolver));
+                self.linters.reuse(old_linters);
+                let mut old = std::mem::replace(&mut self.setup, setup);
+                self.load_error = None;
+                // The audit checks suppressions in every file against the names of all rules
+                let audit_changed = self.config.audit_suppressions
+                    && old.resolver.known_rules() != self.setup.resolver.known_rules();
+                // Otherwise only the rules that changed for a file run on it again
+                for (path, file) in &mut self.files {
+                    let absolute = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+                    file.stale = match (
+                        old.resolver.resolve(path),
+                        self.setup.resolver.resolve(path),
+                    ) {
+                        (Ok(_), Ok(_)) if audit_changed => Stale::Everything,
+                        (Ok(old), Ok(new)) => changed_rules(&old, &new, &absolute),
+                        _ => Stale::Everything,
+                    };
+                }
+                // The new configuration can point to different rule sources and inputs
+                self.rule_stamps = rule_stamps(&self.rule_paths());
+                self.inputs = None;
+            }
+            Err(err) => {
+                self.load_error = Some(format!("{err:#}"));
+                self.rule_stamps = stamps;
+            }
+        }
+        true
+    }
+
+    /// Runs the rules that changed on a file that didn't, reusing its syntax tree and the findings of all other
+    /// rules. Returns `None` if the file needs to be linted from scratch
+    fn relint_rules(
+        &mut self,
+        path: &Path,
+        file: &WatchedFile,
+        changed: &[String],
+    ) -> Option<lintoko::LintResult> {
+        let (Some(parsed), Some(previous)) = (&file.parsed, &file.result) else {
+            return None;
+        };
+        let resolved = self.setup.resolver.resolve(path).ok()?;
+        let absolute = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        debug!("Linting file with changed rules: {}", path.display());
+        let i = self.linters.index(&resolved);
+        self.linters.linters[i]
+            .relint_rules(
+                &self.config,
+                &path.to_string_lossy(),
+                parsed,
+                previous,
+                changed,
+                |rule| resolved.in_scope(rule, &absolute),
+            )
+            .ok()
+    }
+
+    fn lint_file(
+        &mut self,
+        path: &Path,
+        previous: Option<lintoko::ParsedFile>,
+    ) -> (Option<lintoko::ParsedFile>, Option<lintoko::LintResult>) {
+        let name = path.to_string_lossy();
+        let failure = |message: String| lintoko::LintResult {
+            tool_diagnostics: vec![lintoko::ToolDiagnostic {
+                path: Some(name.to_string()),
+                rule: None,
+                message,
+            }],
+            ..lintoko::LintResult::default()
+        };
+        let resolved = match self.setup.resolver.resolve(path) {
+            Ok(resolved) => resolved,
+            Err(err) => return (None, Some(failure(format!("{err:#}")))),
+        };
+        let absolute = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if !resolved.includes_file(&absolute) {
+            return (None, None);
+        }
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => return (None, Some(failure(format!("Failed to read file: {err}")))),
+        };
+        debug!("Linting file: {}", path.display());
+        let i = self.linters.index(&resolved);
+        let linted = self.linters.linters[i].lint_incremental(
+            &self.config,
+            &name,
+            &content,
+            previous,
+            |rule| resolved.in_scope(rule, &absolute),
+        );
+        match linted {
+            Ok((result, parsed)) => (Some(parsed), Some(result)),
+            Err(err) => (None, Some(failure(format!("{err:#}")))),
+        }
+    }
+
+    /// Lints the files that are new, changed, or stale, and forgets about deleted ones. Returns whether any
+    /// file was linted or deleted
+    fn lint_changed_files(&mut self) -> bool {
+        let (paths, expanded) = match self.inputs.take() {
+            Some((paths, expanded)) if expanded.elapsed() < GLOB_INTERVAL => (paths, expanded),
+            // All inputs can be gone for a moment, e.g. while switching branches
+            _ => (
+                expand_inputs(&self.setup.inputs).unwrap_or_default(),
+                Instant::now(),
+            ),
+        };
+        let before = self.files.len();
+        self.files.retain(|path, _| paths.contains(path));
+        let mut changed = self.files.len() != before;
+        for path in &paths {
+            let stamp = stamp(path);
+            let file = self.files.remove(path);
+            if stamp.is_none() {
+                // Deleted since the inputs were expanded
+                changed |= file.is_some();
+                continue;
+            }
+            let (parsed, result) = match file {
+                Some(file) if file.stamp == stamp => match &file.stale {
+                    Stale::Nothing => {
+                        self.files.insert(path.clone(), file);
+                        continue;
+                    }
+                    Stale::Rules(rules) => match self.relint_rules(path, &file, rules) {
+                        Some(result) => (file.parsed, Some(result)),
+                        None => self.lint_file(path, file.parsed),
+                    },
+                    Stale::Everything => self.lint_file(path, file.parsed),
+                },
+                file => self.lint_file(path, file.and_then(|file| file.parsed)),
+            };
+            self.files.insert(
+                path.clone(),
+                WatchedFile {
+                    stamp,
+                    stale: Stale::Nothing,
+                    parsed,
+                    result,
+                },
+            );
+            changed = true;
+        }
+        self.inputs = Some((paths, expanded));
+        changed
+    }
+
+    /// Clears the screen and prints the findings in all files, followed by a summary line
+    fn render(&self) -> Result<()> {
+        let mut out = std::io::stderr().lock();
+        // Clears the screen and moves the cursor to the top left
+        write!(out, "\x1b[2J\x1b[H")?;
+        let mut reporter = lintoko::Reporter::new(self.config.clone(), &mut out);
+        let mut tool_diagnostics = self.linters.rule_errors.clone();
+        let (mut errors, mut warnings, mut files) = (0, 0, 0);
+        for (path, file) in &self.files {
+            let Some(result) = &file.result else {
+                continue;
+            };
+            files += 1;
+            errors += result.error_count;
+            warnings += result.warning_count;
+            tool_diagnostics.extend_from_slice(&result.tool_diagnostics);
+            let source = file.parsed.as_ref().map_or("", |p| p.source());
+            reporter.add(&path.to_string_lossy(), source, result)?;
+        }
+        reporter.finish(&self.setup.resolver.all_rules())?;
+        drop(out);
+        report_tool_diagnostics(&tool_diagnostics);
+        if let Some(err) = &self.load_error {
+            eprintln!("Error: Failed to reload the rules, still using the previous ones: {err}");
+        }
+        eprintln!(
+            "[{}] Found {errors} errors and {warnings} warnings in {files} files. Watching for changes...",
+            jiff::Zoned::now().strftime("%H:%M:%S")
+        );
+        Ok(())
+    }
+}
+
+/// Lints all files, and then again whenever files or rules change. Only returns on errors in the initial setup
+pub(crate) fn watch(args: &Args, config: lintoko::Config) -> Result<ExitCode> {
+    let setup = Watcher::load(args).context(ConfigError)?;
+    let format = match args.formats.as_slice() {
+        [] => setup.cwd_config.format().unwrap_or_default(),
+        [format] if format.path.is_none() => format.format,
+        _ => return Err(anyhow!("`--watch` only prints a single format").context(ConfigError)),
+    };
+    if format.is_aggregated() {
+        return Err(anyhow!(
+            "`--watch` only supports the pretty, text, compact and github formats"
+        )
+        .context(ConfigError));
+    }
+    let mut watcher = Watcher {
+        args,
+        config: lintoko::Config { format, ..config },
+        linters: Linters::new(&setup.resolver),
+        setup,
+        rule_stamps: BTreeMap::new(),
+        load_error: None,
+        inputs: None,
+        files: BTreeMap::new(),
+    };
+    watcher.rule_stamps = rule_stamps(&watcher.rule_paths());
+    watcher.lint_changed_files();
+    watcher.render()?;
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        // Both need to run, so they're not short-circuited
+        let reloaded = watcher.reload_rules();
+        let changed = watcher.lint_changed_files();
+        if reloaded || changed {
+            watcher.render()?;
+        }
+    }
+}
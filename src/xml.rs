@@ -0,0 +1,155 @@
+use crate::report::Report;
+use crate::{RuleSeverity, char_column};
+use anyhow::Result;
+use std::io::Write;
+
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Checkstyle has no hint severity
+fn checkstyle_severity(severity: RuleSeverity) -> String {
+    match severity {
+        RuleSeverity::Off | RuleSeverity::Hint => "info".to_string(),
+        severity => severity.to_string(),
+    }
+}
+
+/// Writes the report in the Checkstyle XML format, with one `<file>` element per linted file
+pub(crate) fn write_checkstyle(report: &Report, mut out: impl Write) -> Result<()> {
+    writeln!(out, "{XML_DECLARATION}")?;
+    writeln!(out, r#"<checkstyle version="4.3">"#)?;
+    for file in &report.files {
+        writeln!(out, r#"  <file name="{}">"#, escape(&file.path))?;
+        for d in &file.diagnostics {
+            writeln!(
+                out,
+                r#"    <error line="{}" column="{}" severity="{}" message="{}" source="{}"/>"#,
+                d.range.start_point.row + 1,
+                char_column(&file.source, d.range.start_byte, d.range.start_point),
+                checkstyle_severity(d.severity),
+                escape(&d.description),
+                escape(&d.rule),
+            )?;
+        }
+        writeln!(out, "  </file>")?;
+    }
+    writeln!(out, "</checkstyle>")?;
+    Ok(())
+}
+
+/// Writes the report in the JUnit XML format. Every linted file is a testcase, and every diagnostic one of its failures
+pub(crate) fn write_junit(report: &Report, mut out: impl Write) -> Result<()> {
+    let tests = report.files.len();
+    let failures = report
+        .files
+        .iter()
+        .filter(|f| f.diagnostics.iter().any(|d| !d.severity.is_advisory()))
+        .count();
+    let name = env!("CARGO_PKG_NAME");
+    writeln!(out, "{XML_DECLARATION}")?;
+    writeln!(
+        out,
+        r#"<testsuites name="{name}" tests="{tests}" failures="{failures}" errors="0">"#
+    )?;
+    writeln!(
+        out,
+        r#"  <testsuite name="{name}" tests="{tests}" failures="{failures}" errors="0">"#
+    )?;
+    for file in &report.files {
+        let path = escape(&file.path);
+        // Hints and infos don't fail the testcase
+        let mut failing = file
+            .diagnostics
+            .iter()
+            .filter(|d| !d.severity.is_advisory())
+            .peekable();
+        if failing.peek().is_none() {
+            writeln!(out, r#"    <testcase name="{path}" classname="{name}"/>"#)?;
+            continue;
+        }
+        writeln!(out, r#"    <testcase name="{path}" classname="{name}">"#)?;
+        for d in failing {
+            writeln!(
+                out,
+                r#"      <failure message="{}" type="{}">{path}:{}:{} {}: {}</failure>"#,
+                escape(&d.description),
+                escape(&d.rule),
+                d.range.start_point.row + 1,
+                char_column(&file.source, d.range.start_byte, d.range.start_point),
+                d.severity,
+                escape(&d.description),
+            )?;
+        }
+        writeln!(out, "    </testcase>")?;
+    }
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::report::test::{pun_fields, render};
+    use crate::{Config, OutputFormat};
+
+    fn xml(format: OutputFormat) -> String {
+        let config = Config {
+            format,
+            ..Config::default()
+        };
+        let files = [("a&b.mo", "{ x = x }"), ("clean.mo", "")];
+        String::from_utf8(render(&config, &pun_fields(), &files)).unwrap()
+    }
+
+    #[test]
+    fn escapes_xml() {
+        assert_eq!(
+            escape(r#"<a href="x">'&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn it_writes_checkstyle() {
+        let xml = xml(OutputFormat::Checkstyle);
+        assert!(xml.contains(r#"<file name="a&amp;b.mo">"#), "{xml}");
+        assert!(
+            xml.contains(r#"<error line="1" column="3" severity="warning" message="Use field punning to to avoid repetition: Replace `{ x = x }` with `{ x }`" source="pun-fields"/>"#),
+            "{xml}"
+        );
+        assert!(xml.contains(r#"<file name="clean.mo">"#), "{xml}");
+    }
+
+    #[test]
+    fn it_writes_junit() {
+        let xml = xml(OutputFormat::Junit);
+        assert!(
+            xml.contains(r#"<testsuite name="lintoko" tests="2" failures="1" errors="0">"#),
+            "{xml}"
+        );
+        assert!(
+            xml.contains(r#"<failure message="Use field punning to to avoid repetition: Replace `{ x = x }` with `{ x }`" type="pun-fields">a&amp;b.mo:1:3 warning: "#),
+            "{xml}"
+        );
+        assert!(
+            xml.contains(r#"<testcase name="clean.mo" classname="lintoko"/>"#),
+            "{xml}"
+        );
+    }
+}